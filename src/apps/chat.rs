@@ -1,7 +1,7 @@
 use crate::error::Result;
+use crate::subscription::SubscriptionHandle;
 use crate::traits::messaging::{AuthoredMessage, Message, Messaging};
 use crate::Channel;
-use crossbeam::channel::Receiver;
 
 /// A struct that provides an interface for interacting with Urbit chats
 pub struct Chat<'a> {
@@ -40,18 +40,19 @@ impl<'a> Chat<'a> {
         self.export_authored_messages(chat_ship, chat_name)
     }
 
-    /// Subscribe to and watch for messages. This method returns a `Receiver` with the
-    /// `AuthoredMessage`s that are posted after subscribing. Simply call `receiver.try_recv()`
+    /// Subscribe to and watch for messages. This method returns a `SubscriptionHandle` with the
+    /// `AuthoredMessage`s that are posted after subscribing. Simply call `handle.try_recv()`
     /// to read the next `AuthoredMessage` if one has been posted.
     ///
     /// Technical Note: This method actually creates a new `Channel` with your Urbit Ship, and spawns a new unix thread
     /// locally that processes all messages on said channel. This is required due to borrowing mechanisms in Rust, however
     /// on the plus side this makes it potentially more performant by each subscription having it's own unix thread.
+    /// The thread stops and deletes its `Channel` once the handle is dropped or shut down.
     pub fn subscribe_to_chat(
         &mut self,
         chat_ship: &str,
         chat_name: &str,
-    ) -> Result<Receiver<AuthoredMessage>> {
+    ) -> Result<SubscriptionHandle<AuthoredMessage>> {
         self.subscribe_to_messages(chat_ship, chat_name)
     }
 }
@@ -1,6 +1,8 @@
 use crate::apps::notebook::Comment;
 use crate::graph::NodeContents;
 use crate::helper::{get_current_da_time, get_current_time};
+use crate::subscription::SubscriptionHandle;
+use crate::traits::messaging::check_resource_json;
 use crate::AuthoredMessage;
 use crate::{Channel, Node, Result, UrbitAPIError};
 
@@ -43,28 +45,37 @@ impl Link {
     /// Convert from a `Node` to a `Link`
     pub fn from_node(node: &Node) -> Result<Link> {
         let mut comments: Vec<Comment> = vec![];
-        // Check the to see if the children exist
-        if node.children.len() > 0 && node.children[0].children.len() > 0 {
-            // Find the latest revision of each of the comments
-            for comment_node in &node.children {
-                let mut latest_comment_revision_node = comment_node.children[0].clone();
-                for revision_node in &comment_node.children {
-                    if revision_node.index_tail() > latest_comment_revision_node.index_tail() {
-                        latest_comment_revision_node = revision_node.clone();
-                    }
+        // Find the latest revision of each of the comments
+        for comment_node in &node.children {
+            let latest_comment_revision_node = comment_node
+                .children
+                .iter()
+                .max_by_key(|r| revision_number(&r.index));
+            match latest_comment_revision_node {
+                Some(revision_node) => comments.push(Comment::from_node(revision_node)),
+                // Comments without revisions hold their contents directly
+                None if !comment_node.contents.is_empty() => {
+                    comments.push(Comment::from_node(comment_node))
                 }
-                comments.push(Comment::from_node(&latest_comment_revision_node));
+                None => {}
             }
         }
 
-        // Acquire the title, which is the first item in the content_list
-        let title = format!("{}", node.contents.content_list[0]["text"]);
-        // Acquire the url, which is the second item in the content_list
-        let url = format!("{}", node.contents.content_list[1]["url"]);
+        // Acquire the title, which is the first item in the content_list,
+        // and the url, which is the second item in the content_list
+        let content_list = &node.contents.content_list;
+        if content_list.len() < 2
+            || !content_list[0]["text"].is_string()
+            || !content_list[1]["url"].is_string()
+        {
+            return Err(UrbitAPIError::InvalidLinkGraphNode(node.to_json().dump()));
+        }
+        let title = format!("{}", content_list[0]["text"]);
+        let url = format!("{}", content_list[1]["url"]);
         let author = node.author.clone();
         let time_sent = node.time_sent_formatted();
 
-        // Create the note
+        // Create the link
         Ok(Link::new(
             &title,
             &author,
@@ -112,12 +123,262 @@ impl<'a> Collection<'a> {
         let link_contents = NodeContents::new().add_text(title).add_url(url);
         let link_node = gs.new_node(&link_contents);
 
-        if let Ok(_) = gs.add_node(collection_ship, collection_name, &link_node) {
-            Ok(link_node.index)
-        } else {
-            Err(UrbitAPIError::FailedToCreateNote(
-                link_node.to_json().dump(),
-            ))
+        gs.add_node(collection_ship, collection_name, &link_node)?;
+        Ok(link_node.index)
+    }
+
+    /// Add a new comment to a link in the specified Collection.
+    /// `link_index` can be the index of the link, or of any comment on the link.
+    /// Returns the index of the newly created first revision of the comment.
+    pub fn add_link_comment(
+        &mut self,
+        collection_ship: &str,
+        collection_name: &str,
+        link_index: &str,
+        comment: &NodeContents,
+    ) -> Result<String> {
+        let link_root_index = link_root_index(link_index)?;
+        let mut gs = self.channel.graph_store();
+        let unix_time = get_current_time();
+
+        // make the root node for the comment, with the initial revision as its child
+        let cmt_root_index = format!("{}/{}", link_root_index, get_current_da_time());
+        let cmt_rev_index = format!("{}/1", cmt_root_index);
+        let cmt_root_node = gs
+            .new_node_specified(&cmt_root_index, unix_time, &NodeContents::new())
+            .add_child(&gs.new_node_specified(&cmt_rev_index, unix_time, comment));
+
+        gs.add_node(collection_ship, collection_name, &cmt_root_node)?;
+        Ok(cmt_rev_index)
+    }
+
+    /// Update an existing comment on a link. `comment_index` can be the comment root index,
+    /// or the index of any revision of the comment.
+    /// Returns the index of the new comment revision.
+    pub fn update_link_comment(
+        &mut self,
+        collection_ship: &str,
+        collection_name: &str,
+        comment_index: &str,
+        comment: &NodeContents,
+    ) -> Result<String> {
+        let cmt_root_index = comment_root_index(comment_index)?;
+        let mut gs = self.channel.graph_store();
+        let cmt_root_node = gs.get_node(collection_ship, collection_name, &cmt_root_index)?;
+
+        // build the index of the next revision
+        let latest_revision = cmt_root_node
+            .children
+            .iter()
+            .map(|r| revision_number(&r.index))
+            .max()
+            .unwrap_or(0);
+        let cmt_new_index = format!("{}/{}", cmt_root_index, latest_revision + 1);
+
+        let node = gs.new_node_specified(&cmt_new_index, get_current_time(), comment);
+        gs.add_node(collection_ship, collection_name, &node)?;
+        Ok(node.index)
+    }
+
+    /// Remove a link (including all of its comments) from the specified Collection.
+    /// Returns the list of indices which were removed.
+    pub fn remove_link(
+        &mut self,
+        collection_ship: &str,
+        collection_name: &str,
+        link_index: &str,
+    ) -> Result<Vec<String>> {
+        let link_root_index = link_root_index(link_index)?;
+        self.remove_node_tree(collection_ship, collection_name, &link_root_index)
+    }
+
+    /// Remove a comment (including all of its revisions) from a link.
+    /// `comment_index` can be the comment root index, or the index of any revision of the comment.
+    /// Returns the list of indices which were removed.
+    pub fn remove_link_comment(
+        &mut self,
+        collection_ship: &str,
+        collection_name: &str,
+        comment_index: &str,
+    ) -> Result<Vec<String>> {
+        let cmt_root_index = comment_root_index(comment_index)?;
+        self.remove_node_tree(collection_ship, collection_name, &cmt_root_index)
+    }
+
+    /// Subscribe to and watch for new links. This method returns a `SubscriptionHandle` with the
+    /// `Link`s that are posted to the Collection after subscribing. Simply call `handle.try_recv()`
+    /// to read the next `Link` if one has been posted.
+    ///
+    /// Technical Note: This method actually creates a new `Channel` with your Urbit Ship, and spawns a new unix thread
+    /// locally that processes all messages on said channel. The thread stops and deletes its `Channel` once
+    /// the handle is dropped or shut down.
+    pub fn subscribe_to_collection(
+        &mut self,
+        collection_ship: &str,
+        collection_name: &str,
+    ) -> Result<SubscriptionHandle<Link>> {
+        let collection_ship = collection_ship.to_string();
+        let collection_name = collection_name.to_string();
+        // Creating a new Ship Interface Channel to pass into the new thread
+        let new_channel = self.channel.ship_interface.create_channel()?;
+
+        SubscriptionHandle::spawn(new_channel, "graph-store", "/updates", move |json| {
+            // Skip updates for other resources
+            if !check_resource_json(&collection_ship, &collection_name, json) {
+                return None;
+            }
+            // Only top level nodes are links, deeper nodes are comments
+            let node = Node::from_graph_update_json(json).ok()?;
+            if node.index.matches('/').count() != 1 {
+                return None;
+            }
+            Link::from_node(&node).ok()
+        })
+    }
+
+    /// Internal method which removes a node and all of its descendants in a single poke.
+    /// Returns the list of indices which were removed.
+    fn remove_node_tree(
+        &mut self,
+        collection_ship: &str,
+        collection_name: &str,
+        root_index: &str,
+    ) -> Result<Vec<String>> {
+        let mut gs = self.channel.graph_store();
+        let root_node = gs.get_node(collection_ship, collection_name, root_index)?;
+
+        // collect every descendant, with children ahead of their parents
+        let mut indices = vec![];
+        collect_indices(&root_node, &mut indices);
+        gs.remove_posts(collection_ship, collection_name, &indices)?;
+
+        Ok(indices)
+    }
+}
+
+/// Collects the indices of a node and all of its descendants, with children ahead of their parents
+fn collect_indices(node: &Node, indices: &mut Vec<String>) {
+    for child in &node.children {
+        collect_indices(child, indices);
+    }
+    indices.push(node.index.clone());
+}
+
+/// Extracts the index of a link from any index of the link or of its comments
+fn link_root_index(index: &str) -> Result<String> {
+    let index_split: Vec<&str> = index.split('/').collect();
+    if index_split.len() < 2 || !index_split[0].is_empty() || index_split[1].is_empty() {
+        return Err(UrbitAPIError::InvalidLinkGraphNode(index.to_string()));
+    }
+    Ok(format!("/{}", index_split[1]))
+}
+
+/// Extracts the root index of a comment from the comment root index or the index of any revision
+fn comment_root_index(index: &str) -> Result<String> {
+    let index_split: Vec<&str> = index.split('/').collect();
+    if index_split.len() < 3 || !index_split[0].is_empty() || index_split[2].is_empty() {
+        return Err(UrbitAPIError::InvalidLinkGraphNode(index.to_string()));
+    }
+    Ok(format!("/{}/{}", index_split[1], index_split[2]))
+}
+
+/// Parses the final section of an index as a revision number (0 if not a number)
+fn revision_number(index: &str) -> u64 {
+    index
+        .rsplit('/')
+        .next()
+        .and_then(|tail| tail.parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use json::object;
+
+    /// Creates a node authored by `~zod` with the given index and contents
+    fn test_node(index: &str, contents: NodeContents) -> Node {
+        Node::new(
+            index.to_string(),
+            "zod".to_string(),
+            1622729109000,
+            vec![],
+            contents,
+            None,
+        )
+    }
+
+    #[test]
+    // Verify that a link is parsed along with the latest revision of its comments
+    fn can_parse_link() {
+        let mut comment = test_node("/1/5", NodeContents::new());
+        comment
+            .children
+            .push(test_node("/1/5/1", NodeContents::new().add_text("first")));
+        comment
+            .children
+            .push(test_node("/1/5/2", NodeContents::new().add_text("second")));
+        let mut node = test_node(
+            "/1",
+            NodeContents::new()
+                .add_text("Urbit")
+                .add_url("https://urbit.org"),
+        );
+        node.children.push(comment);
+
+        let link = Link::from_node(&node).unwrap();
+        assert_eq!(link.title, "Urbit");
+        assert_eq!(link.url, "https://urbit.org");
+        assert_eq!(link.index, "/1");
+        assert_eq!(link.comments.len(), 1);
+        assert_eq!(
+            link.comments[0].contents.to_formatted_string().trim(),
+            "second"
+        );
+    }
+
+    #[test]
+    // Verify that links with missing or malformed contents are rejected rather than panicking
+    fn rejects_malformed_links() {
+        let malformed = vec![
+            NodeContents::new(),
+            NodeContents::new().add_text("Urbit"),
+            NodeContents::from_json(vec![
+                object! {"text": 5},
+                object! {"url": "https://urbit.org"},
+            ]),
+            NodeContents::from_json(vec![object! {"text": "Urbit"}, object! {"url": null}]),
+            NodeContents::new()
+                .add_url("https://urbit.org")
+                .add_text("Urbit"),
+        ];
+        for contents in malformed {
+            assert!(matches!(
+                Link::from_node(&test_node("/1", contents)),
+                Err(UrbitAPIError::InvalidLinkGraphNode(_))
+            ));
+        }
+    }
+
+    #[test]
+    // Verify that link and comment root indexes are extracted from any of their indexes
+    fn can_find_root_indexes() {
+        assert_eq!(link_root_index("/1").unwrap(), "/1");
+        assert_eq!(link_root_index("/1/5/2").unwrap(), "/1");
+        assert_eq!(comment_root_index("/1/5").unwrap(), "/1/5");
+        assert_eq!(comment_root_index("/1/5/2").unwrap(), "/1/5");
+
+        for index in &["", "/", "x/1", "//5"] {
+            assert!(matches!(
+                link_root_index(index),
+                Err(UrbitAPIError::InvalidLinkGraphNode(_))
+            ));
+        }
+        for index in &["", "/", "x/1", "/1", "/1/", "x/1/5"] {
+            assert!(matches!(
+                comment_root_index(index),
+                Err(UrbitAPIError::InvalidLinkGraphNode(_))
+            ));
         }
     }
 }
@@ -0,0 +1,524 @@
+use crate::helper::get_current_time;
+use crate::subscription::SubscriptionHandle;
+use crate::traits::messaging::AuthoredMessage;
+use crate::{Channel, Result, UrbitAPIError};
+use json::{object, JsonValue};
+
+/// A struct that provides an interface for interacting with a ship's contact-store
+pub struct Contacts<'a> {
+    pub channel: &'a mut Channel,
+}
+
+/// A struct that represents the profile of a ship stored in contact-store
+#[derive(Clone, Debug)]
+pub struct Contact {
+    /// The @p of the ship the profile belongs to (with a leading ~)
+    pub ship: String,
+    pub nickname: String,
+    pub bio: String,
+    pub status: String,
+    /// The sigil color of the profile, encoded as `@ux` (ex. `0xff.ffff`)
+    pub color: String,
+    pub avatar: Option<String>,
+    pub cover: Option<String>,
+    /// The groups which are pinned to the profile, as `/ship/name` resource paths
+    pub groups: Vec<String>,
+    pub last_updated: u64,
+}
+
+/// An `AuthoredMessage` along with the nickname of its author from contact-store
+#[derive(Clone, Debug)]
+pub struct NicknamedMessage {
+    pub message: AuthoredMessage,
+    /// The nickname of the author, if they have one set
+    pub author_nickname: Option<String>,
+}
+
+impl NicknamedMessage {
+    /// Returns the nickname of the author if one is set, otherwise the author @p
+    pub fn author_display_name(&self) -> String {
+        match &self.author_nickname {
+            Some(nickname) => nickname.clone(),
+            None => with_sig(&self.message.author),
+        }
+    }
+}
+
+/// A single field of a profile which can be edited
+#[derive(Clone, Debug)]
+pub enum ContactField {
+    Nickname(String),
+    Bio(String),
+    Status(String),
+    Color(String),
+    Avatar(Option<String>),
+    Cover(Option<String>),
+    AddGroup(String, String),
+    RemoveGroup(String, String),
+}
+
+/// An update to a profile which was received from contact-store
+#[derive(Clone, Debug)]
+pub enum ContactUpdate {
+    /// A new contact was added (or an existing one was fully replaced)
+    Add(Contact),
+    /// A single field of a contact was edited. Contains the ship and the edited field.
+    Edit(String, ContactField),
+    /// A contact was removed. Contains the ship.
+    Remove(String),
+}
+
+impl Contact {
+    /// Convert from a contact `JsonValue` as found in contact-store into a `Contact`
+    pub fn from_json(ship: &str, json: &JsonValue) -> Result<Contact> {
+        if !json.is_object() {
+            return Err(UrbitAPIError::InvalidContactJSON(json.dump()));
+        }
+
+        let mut groups = vec![];
+        for group in json["groups"].members() {
+            if let Some(g) = group.as_str() {
+                groups.push(g.to_string());
+            }
+        }
+
+        Ok(Contact {
+            ship: with_sig(ship),
+            nickname: json["nickname"].as_str().unwrap_or("").to_string(),
+            bio: json["bio"].as_str().unwrap_or("").to_string(),
+            status: json["status"].as_str().unwrap_or("").to_string(),
+            color: json["color"].as_str().unwrap_or("0x0").to_string(),
+            avatar: json["avatar"].as_str().map(|s| s.to_string()),
+            cover: json["cover"].as_str().map(|s| s.to_string()),
+            groups,
+            last_updated: json["last-updated"].as_u64().unwrap_or(0),
+        })
+    }
+
+    /// Returns the nickname if one is set, otherwise the ship @p
+    pub fn display_name(&self) -> String {
+        if self.nickname.is_empty() {
+            self.ship.clone()
+        } else {
+            self.nickname.clone()
+        }
+    }
+}
+
+impl ContactField {
+    /// Converts to the `edit-field` `JsonValue` expected by contact-store
+    pub fn to_json(&self) -> JsonValue {
+        match self {
+            ContactField::Nickname(s) => object! {"nickname": s.clone()},
+            ContactField::Bio(s) => object! {"bio": s.clone()},
+            ContactField::Status(s) => object! {"status": s.clone()},
+            ContactField::Color(s) => object! {"color": s.clone()},
+            ContactField::Avatar(s) => object! {"avatar": s.clone()},
+            ContactField::Cover(s) => object! {"cover": s.clone()},
+            ContactField::AddGroup(ship, name) => object! {
+                "add-group": {
+                    "ship": ship.clone(),
+                    "name": name.clone()
+                }
+            },
+            ContactField::RemoveGroup(ship, name) => object! {
+                "remove-group": {
+                    "ship": ship.clone(),
+                    "name": name.clone()
+                }
+            },
+        }
+    }
+
+    /// Convert from an `edit-field` `JsonValue` into a `ContactField`
+    pub fn from_json(json: &JsonValue) -> Option<ContactField> {
+        let (key, val) = json.entries().next()?;
+        let text = || val.as_str().map(|s| s.to_string());
+        let resource = || {
+            Some((
+                val["ship"].as_str()?.to_string(),
+                val["name"].as_str()?.to_string(),
+            ))
+        };
+        match key {
+            "nickname" => Some(ContactField::Nickname(text()?)),
+            "bio" => Some(ContactField::Bio(text()?)),
+            "status" => Some(ContactField::Status(text()?)),
+            "color" => Some(ContactField::Color(text()?)),
+            "avatar" => Some(ContactField::Avatar(text())),
+            "cover" => Some(ContactField::Cover(text())),
+            "add-group" => resource().map(|(s, n)| ContactField::AddGroup(s, n)),
+            "remove-group" => resource().map(|(s, n)| ContactField::RemoveGroup(s, n)),
+            _ => None,
+        }
+    }
+}
+
+impl ContactUpdate {
+    /// Convert from a `contact-update` `JsonValue` received via subscription
+    pub fn from_json(json: &JsonValue) -> Option<ContactUpdate> {
+        let update = &json["contact-update"];
+        if update["add"].is_object() {
+            let ship = update["add"]["ship"].as_str()?;
+            let contact = Contact::from_json(ship, &update["add"]["contact"]).ok()?;
+            return Some(ContactUpdate::Add(contact));
+        }
+        if update["edit"].is_object() {
+            let ship = update["edit"]["ship"].as_str()?;
+            let field = ContactField::from_json(&update["edit"]["edit-field"])?;
+            return Some(ContactUpdate::Edit(with_sig(ship), field));
+        }
+        if update["remove"].is_object() {
+            let ship = update["remove"]["ship"].as_str()?;
+            return Some(ContactUpdate::Remove(with_sig(ship)));
+        }
+        None
+    }
+}
+
+impl<'a> Contacts<'a> {
+    /// Acquire all of the contacts stored in contact-store on the connected ship
+    pub fn get_all_contacts(&mut self) -> Result<Vec<Contact>> {
+        let json = self
+            .channel
+            .ship_interface
+            .scry_json("contact-store", "/all")?;
+
+        let rolodex = &json["contact-update"]["initial"]["rolodex"];
+        let mut contacts = vec![];
+        for (ship, contact_json) in rolodex.entries() {
+            contacts.push(Contact::from_json(ship, contact_json)?);
+        }
+        contacts.sort_by(|a, b| a.ship.cmp(&b.ship));
+        Ok(contacts)
+    }
+
+    /// Acquire the contact of a specific ship
+    pub fn get_contact(&mut self, ship: &str) -> Result<Contact> {
+        let ship = with_sig(ship);
+        let path = format!("/contact/{}", ship);
+        let json = self
+            .channel
+            .ship_interface
+            .scry_json("contact-store", &path)?;
+
+        let add = &json["contact-update"]["add"];
+        if add.is_object() {
+            return Contact::from_json(&ship, &add["contact"]);
+        }
+        Contact::from_json(&ship, &json)
+    }
+
+    /// Acquire the profile of the connected ship
+    pub fn get_own_profile(&mut self) -> Result<Contact> {
+        let ship = self.channel.ship_interface.ship_name_with_sig();
+        self.get_contact(&ship)
+    }
+
+    /// Edit a single field of the profile of the connected ship
+    pub fn edit_own_profile(&mut self, field: &ContactField) -> Result<()> {
+        let ship = self.channel.ship_interface.ship_name_with_sig();
+        let prepped_json = object! {
+            "edit": {
+                "ship": ship.clone(),
+                "edit-field": field.to_json(),
+                "timestamp": get_current_time()
+            }
+        };
+
+        let resp = self
+            .channel
+            .poke("contact-store", "contact-update-0", &prepped_json)?;
+
+        if resp.status().as_u16() == 204 {
+            Ok(())
+        } else {
+            Err(UrbitAPIError::from_response(resp))
+        }
+    }
+
+    /// Set the nickname of the connected ship
+    pub fn set_nickname(&mut self, nickname: &str) -> Result<()> {
+        self.edit_own_profile(&ContactField::Nickname(nickname.to_string()))
+    }
+
+    /// Set the bio of the connected ship
+    pub fn set_bio(&mut self, bio: &str) -> Result<()> {
+        self.edit_own_profile(&ContactField::Bio(bio.to_string()))
+    }
+
+    /// Set the status of the connected ship
+    pub fn set_status(&mut self, status: &str) -> Result<()> {
+        self.edit_own_profile(&ContactField::Status(status.to_string()))
+    }
+
+    /// Set the sigil color of the connected ship. `color` is encoded as `@ux` (ex. `0xff.ffff`)
+    pub fn set_color(&mut self, color: &str) -> Result<()> {
+        self.edit_own_profile(&ContactField::Color(color.to_string()))
+    }
+
+    /// Set (or clear with `None`) the avatar image url of the connected ship
+    pub fn set_avatar(&mut self, avatar_url: Option<&str>) -> Result<()> {
+        self.edit_own_profile(&ContactField::Avatar(avatar_url.map(|s| s.to_string())))
+    }
+
+    /// Set (or clear with `None`) the cover image url of the connected ship
+    pub fn set_cover(&mut self, cover_url: Option<&str>) -> Result<()> {
+        self.edit_own_profile(&ContactField::Cover(cover_url.map(|s| s.to_string())))
+    }
+
+    /// Pin a group to the profile of the connected ship
+    pub fn add_group(&mut self, group_ship: &str, group_name: &str) -> Result<()> {
+        self.edit_own_profile(&ContactField::AddGroup(
+            group_ship.to_string(),
+            group_name.to_string(),
+        ))
+    }
+
+    /// Unpin a group from the profile of the connected ship
+    pub fn remove_group(&mut self, group_ship: &str, group_name: &str) -> Result<()> {
+        self.edit_own_profile(&ContactField::RemoveGroup(
+            group_ship.to_string(),
+            group_name.to_string(),
+        ))
+    }
+
+    /// Allow the provided ships to see the profile of the connected ship
+    pub fn allow_ships(&mut self, ships: &[&str]) -> Result<()> {
+        let ships: Vec<String> = ships.iter().map(|s| with_sig(s)).collect();
+        self.poke_sharing(object! {"allow": {"ships": ships}})
+    }
+
+    /// Stop allowing the provided ships to see the profile of the connected ship
+    pub fn disallow_ships(&mut self, ships: &[&str]) -> Result<()> {
+        let ships: Vec<String> = ships.iter().map(|s| with_sig(s)).collect();
+        self.poke_sharing(object! {"disallow": {"ships": ships}})
+    }
+
+    /// Allow all members of a group to see the profile of the connected ship
+    pub fn allow_group(&mut self, group_ship: &str, group_name: &str) -> Result<()> {
+        self.poke_sharing(object! {
+            "allow": {
+                "group": {
+                    "ship": group_ship,
+                    "name": group_name
+                }
+            }
+        })
+    }
+
+    /// Stop allowing members of a group to see the profile of the connected ship
+    pub fn disallow_group(&mut self, group_ship: &str, group_name: &str) -> Result<()> {
+        self.poke_sharing(object! {
+            "disallow": {
+                "group": {
+                    "ship": group_ship,
+                    "name": group_name
+                }
+            }
+        })
+    }
+
+    /// Set whether the profile of the connected ship is public to all ships
+    pub fn set_public(&mut self, public: bool) -> Result<()> {
+        self.poke_sharing(object! {"set-public": public})
+    }
+
+    /// Share the profile of the connected ship directly with another ship
+    pub fn share_profile(&mut self, ship: &str) -> Result<()> {
+        let ship = with_sig(ship);
+        let prepped_json = object! {"share": ship.clone()};
+        let resp = self
+            .channel
+            .poke("contact-push-hook", "contact-share", &prepped_json)?;
+
+        if resp.status().as_u16() == 204 {
+            Ok(())
+        } else {
+            Err(UrbitAPIError::from_response(resp))
+        }
+    }
+
+    /// Pairs each `AuthoredMessage` with the nickname of its author using the
+    /// contacts stored on the connected ship.
+    pub fn attach_nicknames(
+        &mut self,
+        messages: Vec<AuthoredMessage>,
+    ) -> Result<Vec<NicknamedMessage>> {
+        let contacts = self.get_all_contacts()?;
+        Ok(messages
+            .into_iter()
+            .map(|message| {
+                let author = with_sig(&message.author);
+                let author_nickname = contacts
+                    .iter()
+                    .find(|c| c.ship == author && !c.nickname.is_empty())
+                    .map(|c| c.nickname.clone());
+                NicknamedMessage {
+                    message,
+                    author_nickname,
+                }
+            })
+            .collect())
+    }
+
+    /// Subscribe to and watch for profile changes. This method returns a `SubscriptionHandle` with the
+    /// `ContactUpdate`s that take place after subscribing. Simply call `handle.try_recv()`
+    /// to read the next `ContactUpdate` if one has been received.
+    ///
+    /// Technical Note: This method actually creates a new `Channel` with your Urbit Ship, and spawns a new unix thread
+    /// locally that processes all messages on said channel. The thread stops and deletes its `Channel` once
+    /// the handle is dropped or shut down.
+    pub fn subscribe_to_contacts(&mut self) -> Result<SubscriptionHandle<ContactUpdate>> {
+        // Creating a new Ship Interface Channel to pass into the new thread
+        let new_channel = self.channel.ship_interface.create_channel()?;
+        SubscriptionHandle::spawn(
+            new_channel,
+            "contact-store",
+            "/updates",
+            ContactUpdate::from_json,
+        )
+    }
+
+    /// Internal method which pokes contact-store with a sharing permissions update
+    fn poke_sharing(&mut self, prepped_json: JsonValue) -> Result<()> {
+        let resp = self
+            .channel
+            .poke("contact-store", "contact-update-0", &prepped_json)?;
+
+        if resp.status().as_u16() == 204 {
+            Ok(())
+        } else {
+            Err(UrbitAPIError::from_response(resp))
+        }
+    }
+}
+
+/// Adds a leading `~` to a ship @p if it is missing
+fn with_sig(ship: &str) -> String {
+    if ship.starts_with('~') {
+        ship.to_string()
+    } else {
+        format!("~{}", ship)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Verify that a contact is parsed from contact-store json
+    fn can_parse_contact() {
+        let json = json::parse(
+            r#"{
+                "nickname": "Zod",
+                "bio": "The first",
+                "status": "online",
+                "color": "0xff.ffff",
+                "avatar": "https://urbit.org/avatar.png",
+                "cover": null,
+                "groups": ["/ship/~zod/group", 5],
+                "last-updated": 1622729109000
+            }"#,
+        )
+        .unwrap();
+        let contact = Contact::from_json("zod", &json).unwrap();
+        assert_eq!(contact.ship, "~zod");
+        assert_eq!(contact.nickname, "Zod");
+        assert_eq!(contact.bio, "The first");
+        assert_eq!(contact.status, "online");
+        assert_eq!(contact.color, "0xff.ffff");
+        assert_eq!(
+            contact.avatar.as_deref(),
+            Some("https://urbit.org/avatar.png")
+        );
+        assert_eq!(contact.cover, None);
+        assert_eq!(contact.groups, vec!["/ship/~zod/group"]);
+        assert_eq!(contact.last_updated, 1622729109000);
+        assert_eq!(contact.display_name(), "Zod");
+    }
+
+    #[test]
+    // Verify that missing fields of a contact fall back to their defaults
+    fn can_parse_contact_with_missing_fields() {
+        let contact = Contact::from_json("~bus", &object! {}).unwrap();
+        assert_eq!(contact.ship, "~bus");
+        assert_eq!(contact.nickname, "");
+        assert_eq!(contact.color, "0x0");
+        assert_eq!(contact.avatar, None);
+        assert!(contact.groups.is_empty());
+        assert_eq!(contact.last_updated, 0);
+        assert_eq!(contact.display_name(), "~bus");
+
+        assert!(matches!(
+            Contact::from_json("~bus", &JsonValue::Null),
+            Err(UrbitAPIError::InvalidContactJSON(_))
+        ));
+    }
+
+    #[test]
+    // Verify that edited fields are parsed, and that they survive a json round trip
+    fn can_parse_contact_field() {
+        let fields = vec![
+            ContactField::Nickname("Zod".to_string()),
+            ContactField::Bio("bio".to_string()),
+            ContactField::Status("status".to_string()),
+            ContactField::Color("0x0".to_string()),
+            ContactField::Avatar(Some("https://urbit.org".to_string())),
+            ContactField::Avatar(None),
+            ContactField::Cover(None),
+            ContactField::AddGroup("~zod".to_string(), "group".to_string()),
+            ContactField::RemoveGroup("~zod".to_string(), "group".to_string()),
+        ];
+        for field in fields {
+            let parsed = ContactField::from_json(&field.to_json()).unwrap();
+            assert_eq!(format!("{:?}", parsed), format!("{:?}", field));
+        }
+
+        assert!(ContactField::from_json(&object! {}).is_none());
+        assert!(ContactField::from_json(&object! {"unknown": "x"}).is_none());
+        assert!(ContactField::from_json(&object! {"nickname": null}).is_none());
+        assert!(ContactField::from_json(&object! {"add-group": {"ship": "~zod"}}).is_none());
+    }
+
+    #[test]
+    // Verify that contact-store updates are parsed, and malformed ones ignored
+    fn can_parse_contact_update() {
+        let add = object! {
+            "contact-update": {"add": {"ship": "~zod", "contact": {"nickname": "Zod"}}}
+        };
+        match ContactUpdate::from_json(&add) {
+            Some(ContactUpdate::Add(contact)) => {
+                assert_eq!(contact.ship, "~zod");
+                assert_eq!(contact.nickname, "Zod");
+            }
+            update => panic!("unexpected update {:?}", update),
+        }
+
+        let edit = object! {
+            "contact-update": {
+                "edit": {"ship": "zod", "edit-field": {"bio": "hi"}, "timestamp": 0}
+            }
+        };
+        match ContactUpdate::from_json(&edit) {
+            Some(ContactUpdate::Edit(ship, ContactField::Bio(bio))) => {
+                assert_eq!(ship, "~zod");
+                assert_eq!(bio, "hi");
+            }
+            update => panic!("unexpected update {:?}", update),
+        }
+
+        let remove = object! {"contact-update": {"remove": {"ship": "~zod"}}};
+        assert!(matches!(
+            ContactUpdate::from_json(&remove),
+            Some(ContactUpdate::Remove(ship)) if ship == "~zod"
+        ));
+
+        let missing_ship = object! {"contact-update": {"remove": {}}};
+        assert!(ContactUpdate::from_json(&missing_ship).is_none());
+        let missing_contact = object! {"contact-update": {"add": {"ship": "~zod"}}};
+        assert!(ContactUpdate::from_json(&missing_contact).is_none());
+        assert!(ContactUpdate::from_json(&object! {"contact-update": {}}).is_none());
+    }
+}
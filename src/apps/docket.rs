@@ -0,0 +1,351 @@
+use crate::{Channel, Result, UrbitAPIError};
+use json::{object, JsonValue};
+
+/// A struct that provides an interface for reading app metadata from docket & treaty
+pub struct Docket<'a> {
+    pub channel: &'a mut Channel,
+}
+
+/// The app metadata which is published in the `desk.docket` file of a desk
+#[derive(Clone, Debug)]
+pub struct DocketMetadata {
+    pub title: String,
+    pub info: String,
+    /// The tile color of the app, encoded as `@ux` (ex. `0xff.ffff`)
+    pub color: String,
+    pub image: Option<String>,
+    pub version: String,
+    pub website: String,
+    pub license: String,
+    pub href: DocketHref,
+}
+
+/// Where the frontend of an app is served from
+#[derive(Clone, Debug)]
+pub enum DocketHref {
+    /// A glob served by docket. Contains the base path, and the glob location & hash.
+    Glob(String, String, String),
+    /// A path on the ship which serves the app itself
+    Site(String),
+    /// An href which could not be recognized
+    Unknown,
+}
+
+/// The state of the installation of a desk, as tracked by docket
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChargeState {
+    Install,
+    Glob,
+    Site,
+    Suspend,
+    /// Installation failed with the contained error
+    Hung(String),
+    Unknown,
+}
+
+/// An installed app (desk) as tracked by docket
+#[derive(Clone, Debug)]
+pub struct Charge {
+    pub desk: String,
+    pub docket: DocketMetadata,
+    pub state: ChargeState,
+}
+
+/// An app (desk) which a third-party ship publishes, as tracked by treaty
+#[derive(Clone, Debug)]
+pub struct Treaty {
+    /// The publishing ship (with a leading ~)
+    pub ship: String,
+    pub desk: String,
+    /// The hash of the published revision of the desk
+    pub hash: String,
+    pub docket: DocketMetadata,
+}
+
+impl DocketMetadata {
+    /// Convert from a docket `JsonValue` into a `DocketMetadata`
+    pub fn from_json(json: &JsonValue) -> Result<DocketMetadata> {
+        if !json.is_object() {
+            return Err(UrbitAPIError::InvalidDocketJSON(json.dump()));
+        }
+        let text = |key: &str| json[key].as_str().unwrap_or("").to_string();
+
+        Ok(DocketMetadata {
+            title: text("title"),
+            info: text("info"),
+            color: text("color"),
+            image: json["image"].as_str().map(|s| s.to_string()),
+            version: text("version"),
+            website: text("website"),
+            license: text("license"),
+            href: DocketHref::from_json(&json["href"]),
+        })
+    }
+}
+
+impl DocketHref {
+    /// Convert from an href `JsonValue` into a `DocketHref`
+    pub fn from_json(json: &JsonValue) -> DocketHref {
+        let glob = &json["glob"];
+        if glob.is_object() {
+            let reference = &glob["glob-reference"];
+            // The location is either an http url or the ship the glob is synced from over ames
+            let location = &reference["location"];
+            let location = location["http"]
+                .as_str()
+                .or_else(|| location["ames"].as_str())
+                .unwrap_or("");
+            return DocketHref::Glob(
+                glob["base"].as_str().unwrap_or("").to_string(),
+                location.to_string(),
+                reference["hash"].as_str().unwrap_or("").to_string(),
+            );
+        }
+        if let Some(path) = json["site"].as_str() {
+            return DocketHref::Site(path.to_string());
+        }
+        DocketHref::Unknown
+    }
+}
+
+impl ChargeState {
+    /// Convert from a `chad` `JsonValue` into a `ChargeState`
+    pub fn from_json(json: &JsonValue) -> ChargeState {
+        match json.entries().next() {
+            Some(("install", _)) => ChargeState::Install,
+            Some(("glob", _)) => ChargeState::Glob,
+            Some(("site", _)) => ChargeState::Site,
+            Some(("suspend", _)) => ChargeState::Suspend,
+            Some(("hung", err)) => ChargeState::Hung(err.as_str().unwrap_or("").to_string()),
+            _ => ChargeState::Unknown,
+        }
+    }
+}
+
+impl Charge {
+    /// Convert from a charge `JsonValue` into a `Charge`
+    pub fn from_json(desk: &str, json: &JsonValue) -> Result<Charge> {
+        Ok(Charge {
+            desk: desk.to_string(),
+            docket: DocketMetadata::from_json(json)?,
+            state: ChargeState::from_json(&json["chad"]),
+        })
+    }
+}
+
+impl Treaty {
+    /// Convert from a treaty `JsonValue` into a `Treaty`
+    pub fn from_json(json: &JsonValue) -> Result<Treaty> {
+        let ship = json["ship"]
+            .as_str()
+            .ok_or_else(|| UrbitAPIError::InvalidDocketJSON(json.dump()))?;
+        let desk = json["desk"]
+            .as_str()
+            .ok_or_else(|| UrbitAPIError::InvalidDocketJSON(json.dump()))?;
+
+        Ok(Treaty {
+            ship: ship.to_string(),
+            desk: desk.to_string(),
+            hash: json["hash"].as_str().unwrap_or("").to_string(),
+            docket: DocketMetadata::from_json(json)?,
+        })
+    }
+}
+
+impl<'a> Docket<'a> {
+    /// Acquire every app (desk) installed on the connected ship
+    pub fn get_charges(&mut self) -> Result<Vec<Charge>> {
+        let json = self
+            .channel
+            .ship_interface
+            .scry_json("docket", "/charges")?;
+        let mut charges = vec![];
+        for (desk, charge_json) in json["initial"].entries() {
+            charges.push(Charge::from_json(desk, charge_json)?);
+        }
+        Ok(charges)
+    }
+
+    /// Acquire a single app (desk) installed on the connected ship
+    pub fn get_charge(&mut self, desk: &str) -> Result<Charge> {
+        self.get_charges()?
+            .into_iter()
+            .find(|c| c.desk == desk)
+            .ok_or_else(|| UrbitAPIError::FailedToFetchDocket(format!("/charges/{}", desk)))
+    }
+
+    /// Acquire the allies of the connected ship, which are the ships whose
+    /// published apps are tracked. Returns each ally with the list of
+    /// `~ship/desk` apps it publishes.
+    pub fn get_allies(&mut self) -> Result<Vec<(String, Vec<String>)>> {
+        let json = self.channel.ship_interface.scry_json("treaty", "/allies")?;
+        let mut allies = vec![];
+        for (ship, desks_json) in json["ini"].entries() {
+            let desks = desks_json
+                .members()
+                .filter_map(|d| d.as_str().map(|s| s.to_string()))
+                .collect();
+            allies.push((ship.to_string(), desks));
+        }
+        Ok(allies)
+    }
+
+    /// Acquire every treaty (published app) known to the connected ship
+    pub fn get_treaties(&mut self) -> Result<Vec<Treaty>> {
+        let json = self
+            .channel
+            .ship_interface
+            .scry_json("treaty", "/treaties")?;
+        treaties_from_json(&json)
+    }
+
+    /// Acquire the treaties (published apps) of a specific ship
+    pub fn get_ship_treaties(&mut self, ship: &str) -> Result<Vec<Treaty>> {
+        let json = self
+            .channel
+            .ship_interface
+            .scry_json("treaty", &format!("/treaties/{}", ship))?;
+        treaties_from_json(&json)
+    }
+
+    /// Add a ship as an ally, which begins tracking the apps it publishes
+    pub fn add_ally(&mut self, ship: &str) -> Result<()> {
+        self.poke_ally(object! {"add": ship})
+    }
+
+    /// Remove a ship as an ally, which stops tracking the apps it publishes
+    pub fn remove_ally(&mut self, ship: &str) -> Result<()> {
+        self.poke_ally(object! {"del": ship})
+    }
+
+    /// Internal method which pokes treaty with an ally update
+    fn poke_ally(&mut self, prepped_json: JsonValue) -> Result<()> {
+        let resp = self
+            .channel
+            .poke("treaty", "ally-update-0", &prepped_json)?;
+
+        if resp.status().as_u16() == 204 {
+            Ok(())
+        } else {
+            Err(UrbitAPIError::from_response(resp))
+        }
+    }
+}
+
+/// Parses the treaties out of a treaty scry/update `JsonValue`
+fn treaties_from_json(json: &JsonValue) -> Result<Vec<Treaty>> {
+    let mut treaties = vec![];
+    for (_, treaty_json) in json["ini"].entries() {
+        treaties.push(Treaty::from_json(treaty_json)?);
+    }
+    Ok(treaties)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A charge as returned by the docket `/charges` scry, with the given `chad`
+    fn charge_json(chad: JsonValue) -> JsonValue {
+        object! {
+            "title": "Landscape",
+            "info": "A suite of applications",
+            "color": "0xee.5432",
+            "image": null,
+            "version": "1.0.0",
+            "website": "https://tlon.io",
+            "license": "MIT",
+            "href": {
+                "glob": {
+                    "base": "landscape",
+                    "glob-reference": {
+                        "location": {"http": "https://bootstrap.urbit.org/glob.glob"},
+                        "hash": "0v3.abcde"
+                    }
+                }
+            },
+            "chad": chad
+        }
+    }
+
+    #[test]
+    // Verify that glob and site hrefs are parsed
+    fn can_parse_docket_href() {
+        match DocketHref::from_json(&charge_json(JsonValue::Null)["href"]) {
+            DocketHref::Glob(base, location, hash) => {
+                assert_eq!(base, "landscape");
+                assert_eq!(location, "https://bootstrap.urbit.org/glob.glob");
+                assert_eq!(hash, "0v3.abcde");
+            }
+            href => panic!("unexpected href {:?}", href),
+        }
+
+        let ames_glob = object! {
+            "glob": {
+                "base": "webterm",
+                "glob-reference": {"location": {"ames": "~zod"}, "hash": "0v1"}
+            }
+        };
+        assert!(matches!(
+            DocketHref::from_json(&ames_glob),
+            DocketHref::Glob(_, location, _) if location == "~zod"
+        ));
+        assert!(matches!(
+            DocketHref::from_json(&object! {"site": "/apps/grid"}),
+            DocketHref::Site(path) if path == "/apps/grid"
+        ));
+        assert!(matches!(
+            DocketHref::from_json(&JsonValue::Null),
+            DocketHref::Unknown
+        ));
+    }
+
+    #[test]
+    // Verify that the installation states of charges are parsed
+    fn can_parse_charge_state() {
+        let states = vec![
+            (object! {"install": null}, ChargeState::Install),
+            (object! {"glob": null}, ChargeState::Glob),
+            (object! {"site": null}, ChargeState::Site),
+            (object! {"suspend": null}, ChargeState::Suspend),
+            (
+                object! {"hung": "glob failed"},
+                ChargeState::Hung("glob failed".to_string()),
+            ),
+            (object! {"unknown": null}, ChargeState::Unknown),
+            (JsonValue::Null, ChargeState::Unknown),
+        ];
+        for (chad, state) in states {
+            let charge = Charge::from_json("landscape", &charge_json(chad)).unwrap();
+            assert_eq!(charge.state, state);
+        }
+
+        let charge = Charge::from_json("landscape", &charge_json(object! {"glob": null})).unwrap();
+        assert_eq!(charge.desk, "landscape");
+        assert_eq!(charge.docket.title, "Landscape");
+        assert_eq!(charge.docket.color, "0xee.5432");
+        assert_eq!(charge.docket.image, None);
+        assert!(Charge::from_json("landscape", &JsonValue::Null).is_err());
+    }
+
+    #[test]
+    // Verify that treaties are parsed, and rejected without a ship or desk
+    fn can_parse_treaty() {
+        let mut json = charge_json(JsonValue::Null);
+        json["ship"] = "~zod".into();
+        json["desk"] = "landscape".into();
+        json["hash"] = "0v5.12345".into();
+        let treaties = treaties_from_json(&object! {"ini": {"~zod/landscape": json.clone()}});
+        let treaty = &treaties.unwrap()[0];
+        assert_eq!(treaty.ship, "~zod");
+        assert_eq!(treaty.desk, "landscape");
+        assert_eq!(treaty.hash, "0v5.12345");
+        assert_eq!(treaty.docket.version, "1.0.0");
+
+        json.remove("desk");
+        assert!(matches!(
+            Treaty::from_json(&json),
+            Err(UrbitAPIError::InvalidDocketJSON(_))
+        ));
+    }
+}
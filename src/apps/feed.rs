@@ -0,0 +1,240 @@
+use crate::graph::NodeContents;
+use crate::graphstore::Module;
+use crate::helper::{get_current_da_time, get_current_time, index_dec_to_ud};
+use crate::{Channel, Node, Result, UrbitAPIError};
+
+/// A struct that provides an interface for interacting with Urbit group feeds
+/// (graphs which use the `graph-validator-post` module)
+pub struct Feed<'a> {
+    pub channel: &'a mut Channel,
+}
+
+/// A struct that represents a post in a feed, along with all of its (nested) replies
+#[derive(Clone, Debug)]
+pub struct FeedPost {
+    pub author: String,
+    pub contents: NodeContents,
+    pub time_sent: String,
+    pub index: String,
+    pub replies: Vec<FeedPost>,
+}
+
+impl FeedPost {
+    /// Convert from a `Node` (and all of its children) to a `FeedPost`
+    pub fn from_node(node: &Node) -> FeedPost {
+        let mut replies: Vec<FeedPost> = node.children.iter().map(FeedPost::from_node).collect();
+        replies.sort_by(|a, b| a.index.cmp(&b.index));
+
+        FeedPost {
+            author: node.author.clone(),
+            contents: node.contents.clone(),
+            time_sent: node.time_sent_formatted(),
+            index: node.index.clone(),
+            replies,
+        }
+    }
+
+    /// The depth of the post within the thread, where top level posts have a depth of 0
+    pub fn depth(&self) -> usize {
+        self.index.matches('/').count().saturating_sub(1)
+    }
+
+    /// The total number of replies to the post, at any depth
+    pub fn reply_count(&self) -> usize {
+        self.replies.iter().map(|r| 1 + r.reply_count()).sum()
+    }
+
+    /// Renders the post and all of its replies as a human readable tree, with
+    /// each reply indented beneath the post it replies to.
+    pub fn to_formatted_tree(&self) -> String {
+        let mut lines = vec![];
+        self.push_formatted_lines(0, &mut lines);
+        lines.join("\n")
+    }
+
+    /// Internal method which appends the formatted lines of the post and its replies
+    fn push_formatted_lines(&self, indent: usize, lines: &mut Vec<String>) {
+        let prefix = if indent == 0 {
+            "".to_string()
+        } else {
+            format!("{}└─", "  ".repeat(indent - 1))
+        };
+        lines.push(format!(
+            "{}{} - ~{}:{}",
+            prefix,
+            self.time_sent,
+            self.author,
+            self.contents.to_formatted_string()
+        ));
+        for reply in &self.replies {
+            reply.push_formatted_lines(indent + 1, lines);
+        }
+    }
+}
+
+impl<'a> Feed<'a> {
+    /// Create a new feed on the connected ship which is associated with the given group
+    pub fn create_feed(
+        &mut self,
+        feed_name: &str,
+        feed_title: &str,
+        feed_description: &str,
+        group_ship: &str,
+        group_name: &str,
+    ) -> Result<()> {
+        self.channel.graph_store().create_managed_graph(
+            feed_name,
+            feed_title,
+            feed_description,
+            Module::Post,
+            group_ship,
+            group_name,
+        )
+    }
+
+    /// Create a new feed on the connected ship which is not associated with any group
+    pub fn create_unmanaged_feed(
+        &mut self,
+        feed_name: &str,
+        feed_title: &str,
+        feed_description: &str,
+    ) -> Result<()> {
+        self.channel.graph_store().create_unmanaged_graph(
+            feed_name,
+            feed_title,
+            feed_description,
+            Module::Post,
+        )
+    }
+
+    /// Add a new top level post to a feed.
+    /// Returns the index of the post.
+    pub fn post(
+        &mut self,
+        feed_ship: &str,
+        feed_name: &str,
+        contents: &NodeContents,
+    ) -> Result<String> {
+        let mut gs = self.channel.graph_store();
+        let node = gs.new_node(contents);
+
+        gs.add_node(feed_ship, feed_name, &node)?;
+        Ok(node.index)
+    }
+
+    /// Reply to a post (or to another reply, at any depth) in a feed.
+    /// Returns the index of the reply.
+    pub fn reply(
+        &mut self,
+        feed_ship: &str,
+        feed_name: &str,
+        parent_index: &str,
+        contents: &NodeContents,
+    ) -> Result<String> {
+        if !parent_index.starts_with('/') || parent_index.len() < 2 {
+            return Err(UrbitAPIError::InvalidPostGraphNodeIndex(
+                parent_index.to_string(),
+            ));
+        }
+
+        let mut gs = self.channel.graph_store();
+        let reply_index = format!("{}/{}", parent_index, get_current_da_time());
+        let node = gs.new_node_specified(&reply_index, get_current_time(), contents);
+
+        gs.add_node(feed_ship, feed_name, &node)?;
+        Ok(node.index)
+    }
+
+    /// Extracts a feed's graph from the connected ship and parses it into a vector
+    /// of top level `FeedPost`s (with all of their replies), ordered by index.
+    pub fn export_feed(&mut self, feed_ship: &str, feed_name: &str) -> Result<Vec<FeedPost>> {
+        let graph = self.channel.graph_store().get_graph(feed_ship, feed_name)?;
+        let mut posts: Vec<FeedPost> = graph.nodes.iter().map(FeedPost::from_node).collect();
+        posts.sort_by(|a, b| a.index.cmp(&b.index));
+        Ok(posts)
+    }
+
+    /// Fetch a thread given the index of a post (which may itself be a reply), including
+    /// the subset of its direct replies between `start_index` and `end_index`
+    /// (as acquired via `node-children-subset`) and all of their nested replies.
+    pub fn fetch_thread(
+        &mut self,
+        feed_ship: &str,
+        feed_name: &str,
+        post_index: &str,
+        start_index: &str,
+        end_index: &str,
+    ) -> Result<FeedPost> {
+        let mut gs = self.channel.graph_store();
+        let mut node = gs.get_node(feed_ship, feed_name, post_index)?;
+
+        let ud_index = index_dec_to_ud(post_index);
+        let replies = gs.get_node_subset(
+            feed_ship,
+            feed_name,
+            ud_index.trim_start_matches('/'),
+            start_index,
+            end_index,
+        )?;
+        node.children = replies.nodes;
+
+        Ok(FeedPost::from_node(&node))
+    }
+
+    /// Fetch a thread given the index of a post (which may itself be a reply),
+    /// including all of its nested replies.
+    pub fn fetch_full_thread(
+        &mut self,
+        feed_ship: &str,
+        feed_name: &str,
+        post_index: &str,
+    ) -> Result<FeedPost> {
+        let node = self
+            .channel
+            .graph_store()
+            .get_node(feed_ship, feed_name, post_index)?;
+        Ok(FeedPost::from_node(&node))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a node with the given index, author and text
+    fn test_node(index: &str, author: &str, text: &str) -> Node {
+        Node::new(
+            index.to_string(),
+            author.to_string(),
+            1622729109000,
+            vec![],
+            NodeContents::new().add_text(text),
+            None,
+        )
+    }
+
+    #[test]
+    // Verify that nested replies are built into a tree and rendered indented beneath their parents
+    fn can_build_post_tree() {
+        let mut reply = test_node("/1/2", "bus", "reply");
+        reply.children.push(test_node("/1/2/3", "zod", "nested"));
+        let mut post = test_node("/1", "zod", "post");
+        post.children.push(test_node("/1/4", "nec", "second"));
+        post.children.push(reply);
+
+        let post = FeedPost::from_node(&post);
+        assert_eq!(post.depth(), 0);
+        assert_eq!(post.reply_count(), 3);
+        assert_eq!(post.replies[0].index, "/1/2");
+        assert_eq!(post.replies[0].depth(), 1);
+        assert_eq!(post.replies[0].replies[0].depth(), 2);
+        assert_eq!(post.replies[1].reply_count(), 0);
+        assert_eq!(
+            post.to_formatted_tree(),
+            "2021-06-03 14:05:09 - ~zod: post\n\
+             └─2021-06-03 14:05:09 - ~bus: reply\n  \
+             └─2021-06-03 14:05:09 - ~zod: nested\n\
+             └─2021-06-03 14:05:09 - ~nec: second"
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{Channel, Result, UrbitAPIError};
+use json::{object, JsonValue};
+
+/// A struct that provides an interface for managing desks via hood/kiln
+pub struct Hood<'a> {
+    pub channel: &'a mut Channel,
+}
+
+/// A struct that represents the kiln status of an installed desk (a "vat")
+#[derive(Clone, Debug)]
+pub struct Vat {
+    /// The name of the desk
+    pub desk: String,
+    /// The hash of the current revision of the desk
+    pub hash: String,
+    /// Whether the agents of the desk are currently running
+    pub running: bool,
+    /// The ship that the desk is synced from, if any
+    pub source_ship: Option<String>,
+    /// The desk on `source_ship` that the desk is synced from, if any
+    pub source_desk: Option<String>,
+    /// Whether syncing from the source is currently paused
+    pub paused: bool,
+    /// The revision number of the source that was last synced
+    pub aeon: Option<u64>,
+    /// The full json of the vat as returned by kiln
+    pub json: JsonValue,
+}
+
+/// The merge strategy used when merging desks via kiln (`germ` in Hoon)
+#[derive(Clone, Debug)]
+pub enum Germ {
+    Auto,
+    Init,
+    Fine,
+    Meet,
+    Mate,
+    Meld,
+    OnlyThis,
+    OnlyThat,
+    TakeThis,
+    TakeThat,
+    MeetThis,
+    MeetThat,
+}
+
+impl Vat {
+    /// Convert from a vat `JsonValue` as returned by kiln into a `Vat`
+    pub fn from_json(desk: &str, json: &JsonValue) -> Result<Vat> {
+        if !json.is_object() {
+            return Err(UrbitAPIError::InvalidVatJSON(json.dump()));
+        }
+        let rail = &json["arak"]["rail"];
+        let running = match json["zest"].as_str() {
+            Some(zest) => zest == "live",
+            None => json["arak"]["rein"]["liv"].as_bool().unwrap_or(false),
+        };
+
+        Ok(Vat {
+            desk: desk.to_string(),
+            hash: json["hash"].as_str().unwrap_or("").to_string(),
+            running,
+            source_ship: rail["ship"].as_str().map(|s| s.to_string()),
+            source_desk: rail["desk"].as_str().map(|s| s.to_string()),
+            paused: rail["paused"].as_bool().unwrap_or(false),
+            aeon: rail["aeon"].as_u64(),
+            json: json.clone(),
+        })
+    }
+}
+
+impl Germ {
+    /// Convert into the term used by kiln
+    pub fn to_term(&self) -> &'static str {
+        match self {
+            Germ::Auto => "auto",
+            Germ::Init => "init",
+            Germ::Fine => "fine",
+            Germ::Meet => "meet",
+            Germ::Mate => "mate",
+            Germ::Meld => "meld",
+            Germ::OnlyThis => "only-this",
+            Germ::OnlyThat => "only-that",
+            Germ::TakeThis => "take-this",
+            Germ::TakeThat => "take-that",
+            Germ::MeetThis => "meet-this",
+            Germ::MeetThat => "meet-that",
+        }
+    }
+}
+
+impl<'a> Hood<'a> {
+    /// Install a desk from a remote ship onto the connected ship, equivalent to
+    /// `|install ~ship %desk, =local %local-desk` in dojo.
+    pub fn install_desk(
+        &mut self,
+        source_ship: &str,
+        source_desk: &str,
+        local_desk: &str,
+    ) -> Result<()> {
+        let prepped_json = install_json(source_ship, source_desk, local_desk);
+        self.poke_kiln("kiln-install", &prepped_json)
+    }
+
+    /// Uninstall a desk, equivalent to `|uninstall %desk` in dojo
+    pub fn uninstall_desk(&mut self, desk: &str) -> Result<()> {
+        self.poke_kiln("kiln-uninstall", &desk.into())
+    }
+
+    /// Suspend all of the agents of a desk, equivalent to `|suspend %desk` in dojo
+    pub fn suspend_desk(&mut self, desk: &str) -> Result<()> {
+        self.poke_kiln("kiln-suspend", &desk.into())
+    }
+
+    /// Revive all of the agents of a suspended desk, equivalent to `|revive %desk` in dojo
+    pub fn revive_desk(&mut self, desk: &str) -> Result<()> {
+        self.poke_kiln("kiln-revive", &desk.into())
+    }
+
+    /// Set the source of over-the-air updates for the `%base` desk,
+    /// equivalent to `|ota ~ship` in dojo.
+    pub fn set_ota_source(&mut self, source_ship: &str) -> Result<()> {
+        self.install_desk(source_ship, "kids", "base")
+    }
+
+    /// Merge a desk from a (possibly remote) ship into a local desk, equivalent
+    /// to `|merge %local-desk ~ship %source-desk, =gem %germ` in dojo.
+    pub fn merge_desk(
+        &mut self,
+        local_desk: &str,
+        source_ship: &str,
+        source_desk: &str,
+        germ: Germ,
+    ) -> Result<()> {
+        let prepped_json = merge_json(local_desk, source_ship, source_desk, &germ);
+        self.poke_kiln("kiln-merge", &prepped_json)
+    }
+
+    /// Acquire the kiln status of every desk on the connected ship
+    pub fn get_vats(&mut self) -> Result<Vec<Vat>> {
+        let json = self
+            .channel
+            .ship_interface
+            .scry_json("hood", "/kiln/vats")?;
+        let mut vats = vec![];
+        for (desk, vat_json) in json.entries() {
+            vats.push(Vat::from_json(desk, vat_json)?);
+        }
+        Ok(vats)
+    }
+
+    /// Acquire the kiln status of a single desk on the connected ship
+    pub fn get_vat(&mut self, desk: &str) -> Result<Vat> {
+        self.get_vats()?
+            .into_iter()
+            .find(|v| v.desk == desk)
+            .ok_or_else(|| UrbitAPIError::FailedToFetchKilnState(format!("/kiln/vats/{}", desk)))
+    }
+
+    /// Acquire the source of over-the-air updates of the connected ship as raw json
+    pub fn get_ota_source(&mut self) -> Result<JsonValue> {
+        self.channel.ship_interface.scry_json("hood", "/kiln/ota")
+    }
+
+    /// Reset the `+code` of the connected ship, equivalent to `+code %reset` in dojo.
+    ///
+    /// This invalidates the code used to log in, so the `ShipInterface` (and its clones)
+    /// stop logging in again once the session expires, and requests then fail with the
+    /// ship's rejection instead. Once the new code has been read from dojo, pass it to
+    /// `ShipInterface::set_ship_code` to allow logging in again.
+    pub fn reset_code(&mut self) -> Result<()> {
+        let resp = self.channel.poke("hood", "helm-code", &"reset".into())?;
+
+        if resp.status().as_u16() == 204 {
+            self.channel.ship_interface.set_ship_code(None);
+            Ok(())
+        } else {
+            Err(UrbitAPIError::from_response(resp))
+        }
+    }
+
+    /// Internal method which pokes hood with a kiln mark
+    fn poke_kiln(&mut self, mark: &str, prepped_json: &JsonValue) -> Result<()> {
+        let resp = self.channel.poke("hood", mark, prepped_json)?;
+
+        if resp.status().as_u16() == 204 {
+            Ok(())
+        } else {
+            Err(UrbitAPIError::from_response(resp))
+        }
+    }
+}
+
+/// Internal function which builds the json of a `kiln-install` poke
+fn install_json(source_ship: &str, source_desk: &str, local_desk: &str) -> JsonValue {
+    object! {
+        "ship": source_ship,
+        "desk": source_desk,
+        "local": local_desk
+    }
+}
+
+/// Internal function which builds the json of a `kiln-merge` poke
+fn merge_json(local_desk: &str, source_ship: &str, source_desk: &str, germ: &Germ) -> JsonValue {
+    object! {
+        "desk": local_desk,
+        "ship": source_ship,
+        "from": source_desk,
+        "germ": germ.to_term()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Install pokes name the source ship/desk and the local desk
+    fn can_build_install_json() {
+        let json = install_json("~zod", "kids", "base");
+        assert_eq!(
+            json.dump(),
+            r#"{"ship":"~zod","desk":"kids","local":"base"}"#
+        );
+    }
+
+    #[test]
+    // Merge pokes carry the germ as its kiln term
+    fn can_build_merge_json() {
+        let json = merge_json("work", "~bus", "base", &Germ::TakeThat);
+        assert_eq!(
+            json.dump(),
+            r#"{"desk":"work","ship":"~bus","from":"base","germ":"take-that"}"#
+        );
+        assert_eq!(Germ::MeetThis.to_term(), "meet-this");
+        assert_eq!(Germ::Auto.to_term(), "auto");
+    }
+
+    #[test]
+    // Vats are parsed from kiln json, and non-objects are rejected
+    fn can_parse_vat() {
+        let json = json::parse(
+            r#"{"hash":"0v1.abc","zest":"live","arak":{"rail":{"ship":"~zod","desk":"kids","paused":false,"aeon":7}}}"#,
+        )
+        .unwrap();
+        let vat = Vat::from_json("base", &json).unwrap();
+        assert_eq!(vat.desk, "base");
+        assert_eq!(vat.hash, "0v1.abc");
+        assert!(vat.running);
+        assert_eq!(vat.source_ship.as_deref(), Some("~zod"));
+        assert_eq!(vat.source_desk.as_deref(), Some("kids"));
+        assert!(!vat.paused);
+        assert_eq!(vat.aeon, Some(7));
+
+        assert!(Vat::from_json("base", &"nope".into()).is_err());
+    }
+}
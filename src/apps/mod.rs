@@ -1,6 +1,11 @@
 pub mod chat;
 pub mod collections;
+pub mod contacts;
 pub mod dm;
+pub mod docket;
+pub mod feed;
 pub mod harkstore;
+pub mod hood;
 pub mod invitestore;
 pub mod notebook;
+pub mod settings;
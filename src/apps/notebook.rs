@@ -2,6 +2,14 @@ use crate::graph::NodeContents;
 use crate::helper::{get_current_da_time, get_current_time};
 use crate::AuthoredMessage;
 use crate::{Channel, Node, Result, UrbitAPIError};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+/// The line which separates the body of a note from its comments in exported markdown files
+static MARKDOWN_COMMENTS_SEPARATOR: &str = "<!-- comments -->";
 
 /// A struct that provides an interface for interacting with Urbit notebooks
 pub struct Notebook<'a> {
@@ -22,6 +30,28 @@ pub struct Note {
     pub index: String,
 }
 
+/// A struct that represents a single revision of a Note from a Notebook
+#[derive(Clone, Debug)]
+pub struct NoteRevision {
+    pub revision: u64,
+    pub title: String,
+    pub author: String,
+    pub time_sent: String,
+    pub contents: String,
+    pub index: String,
+}
+
+/// The action which was taken for a single markdown file when importing a notebook
+#[derive(Clone, Debug, PartialEq)]
+pub enum NoteImport {
+    /// A new note was created. Contains the index of the first revision.
+    Created(String),
+    /// An existing note was updated. Contains the index of the new revision.
+    Updated(String),
+    /// An existing note already matched the file. Contains the index of the latest revision.
+    Unchanged(String),
+}
+
 /// An internal helper struct for analysing Notebook node indices
 #[derive(Clone, Debug)]
 struct NotebookIndex<'a> {
@@ -123,6 +153,68 @@ impl Note {
             .map(|l| l.to_string())
             .collect()
     }
+
+    /// Convert the Note into a markdown document with YAML front-matter
+    /// (title, author, time, index, revision count) and the comments appended.
+    pub fn to_markdown_document(&self) -> String {
+        let mut front_matter = Hash::new();
+        let mut insert = |key: &str, val: Yaml| {
+            front_matter.insert(Yaml::String(key.to_string()), val);
+        };
+        insert("title", Yaml::String(self.title.clone()));
+        insert("author", Yaml::String(format!("~{}", self.author)));
+        insert("time", Yaml::String(self.time_sent.clone()));
+        insert("index", Yaml::String(self.index.clone()));
+        let revisions = NotebookIndex::new(&self.index).revision().unwrap_or(1);
+        insert("revisions", Yaml::Integer(revisions as i64));
+
+        let mut document = front_matter_to_string(&front_matter);
+        document += &format!("\n{}\n", self.contents.trim_end());
+        if !self.comments.is_empty() {
+            document += &format!("\n{}\n", MARKDOWN_COMMENTS_SEPARATOR);
+            for comment in &self.comments {
+                document += &format!(
+                    "\n**~{}** - {}\n\n{}\n",
+                    comment.author,
+                    comment.time_sent,
+                    comment.contents.to_formatted_string().trim()
+                );
+            }
+        }
+        document
+    }
+}
+
+impl NoteRevision {
+    /// Convert from a note revision `Node` to a `NoteRevision`
+    pub fn from_node(node: &Node) -> Result<NoteRevision> {
+        let revision = NotebookIndex::new(&node.index)
+            .revision()
+            .map_err(|_| UrbitAPIError::InvalidNoteGraphNode(node.to_json().dump()))?;
+        // The title is the first item and the body is the second item of the revision node
+        let content_list = &node.contents.content_list;
+        if content_list.len() < 2 {
+            return Err(UrbitAPIError::InvalidNoteGraphNode(node.to_json().dump()));
+        }
+
+        Ok(NoteRevision {
+            revision,
+            title: format!("{}", content_list[0]["text"]),
+            author: node.author.clone(),
+            time_sent: node.time_sent_formatted(),
+            contents: format!("{}", content_list[1]["text"]),
+            index: node.index.clone(),
+        })
+    }
+
+    /// Produces a line based textual diff from `self` to the `newer` revision.
+    /// The title is included as the first line (prefixed with `# `), and every
+    /// output line is prefixed with `- ` (removed), `+ ` (added), or `  ` (unchanged).
+    pub fn diff(&self, newer: &NoteRevision) -> String {
+        let old_text = format!("# {}\n{}", self.title, self.contents);
+        let new_text = format!("# {}\n{}", newer.title, newer.contents);
+        diff_lines(&old_text, &new_text)
+    }
 }
 
 impl<'a> Notebook<'a> {
@@ -147,6 +239,128 @@ impl<'a> Notebook<'a> {
         Ok(notes)
     }
 
+    /// Exports a Notebook into the directory at `dir_path` (which is created if it does not exist),
+    /// writing one markdown file per note named after the note root index.
+    /// Returns the paths of the files which were written.
+    pub fn export_notebook_to_dir(
+        &mut self,
+        notebook_ship: &str,
+        notebook_name: &str,
+        dir_path: &str,
+    ) -> Result<Vec<String>> {
+        let notes = self.export_notebook(notebook_ship, notebook_name)?;
+        let dir = Path::new(dir_path);
+        fs::create_dir_all(dir)
+            .map_err(|e| UrbitAPIError::FailedToExportNotebook(format!("{}: {}", dir_path, e)))?;
+
+        let mut written = vec![];
+        for note in notes {
+            let root_index = NotebookIndex::new(&note.index).note_root_index();
+            let file_path = dir.join(format!("{}.md", root_index.trim_start_matches('/')));
+            fs::write(&file_path, note.to_markdown_document()).map_err(|e| {
+                UrbitAPIError::FailedToExportNotebook(format!("{}: {}", file_path.display(), e))
+            })?;
+            written.push(file_path.display().to_string());
+        }
+
+        Ok(written)
+    }
+
+    /// Imports every markdown file in the directory at `dir_path` into a Notebook.
+    /// Files whose front-matter `index` matches an existing note update said note (only if the
+    /// title or body changed), and all other files create a new note. The front-matter of files
+    /// which created or updated a note is rewritten with the new index, so importing the same
+    /// directory again does not create duplicate notes.
+    pub fn import_notebook_from_dir(
+        &mut self,
+        notebook_ship: &str,
+        notebook_name: &str,
+        dir_path: &str,
+    ) -> Result<Vec<NoteImport>> {
+        let entries = fs::read_dir(dir_path)
+            .map_err(|e| UrbitAPIError::FailedToImportNotebook(format!("{}: {}", dir_path, e)))?;
+        let mut file_paths = vec![];
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension() == Some(OsStr::new("md")) {
+                file_paths.push(path);
+            }
+        }
+        file_paths.sort();
+
+        let mut imports = vec![];
+        for file_path in file_paths {
+            let import_error = |e: String| {
+                UrbitAPIError::FailedToImportNotebook(format!("{}: {}", file_path.display(), e))
+            };
+            let document =
+                fs::read_to_string(&file_path).map_err(|e| import_error(e.to_string()))?;
+            let (mut front_matter, body, rest) = parse_markdown_document(&document)
+                .ok_or_else(|| import_error("invalid front-matter".to_string()))?;
+
+            let file_stem = file_path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let title = front_matter
+                .get(&Yaml::String("title".to_string()))
+                .and_then(|t| t.as_str())
+                .unwrap_or(&file_stem)
+                .to_string();
+            let index = front_matter
+                .get(&Yaml::String("index".to_string()))
+                .and_then(|i| i.as_str())
+                .map(|i| i.to_string());
+
+            // Find the existing note which matches the front-matter index, if any
+            let existing_note = match &index {
+                Some(idx) if NotebookIndex::new(idx).is_valid() => {
+                    let root_index = NotebookIndex::new(idx).note_root_index();
+                    self.fetch_note(notebook_ship, notebook_name, &root_index)
+                        .ok()
+                }
+                _ => None,
+            };
+
+            let import = match existing_note {
+                Some(note) if note.title == title && note.contents.trim() == body.trim() => {
+                    NoteImport::Unchanged(note.index)
+                }
+                Some(note) => NoteImport::Updated(self.update_note(
+                    notebook_ship,
+                    notebook_name,
+                    &note.index,
+                    &title,
+                    &body,
+                )?),
+                None => NoteImport::Created(self.add_note(
+                    notebook_ship,
+                    notebook_name,
+                    &title,
+                    &body,
+                )?),
+            };
+
+            // Record the new index in the front-matter of the file
+            if let NoteImport::Created(new_index) | NoteImport::Updated(new_index) = &import {
+                let revisions = NotebookIndex::new(new_index).revision().unwrap_or(1);
+                front_matter.insert(
+                    Yaml::String("index".to_string()),
+                    Yaml::String(new_index.clone()),
+                );
+                front_matter.insert(
+                    Yaml::String("revisions".to_string()),
+                    Yaml::Integer(revisions as i64),
+                );
+                let new_document = format!("{}\n{}", front_matter_to_string(&front_matter), rest);
+                fs::write(&file_path, new_document).map_err(|e| import_error(e.to_string()))?;
+            }
+            imports.push(import);
+        }
+
+        Ok(imports)
+    }
+
     /// Fetch a note object given an index `note_index`. This note index can be the root index of the note
     /// or any of the child indexes of the note. If a child index for a specific revision of the note is passed
     /// then that revision will be fetched, otherwise latest revision is the default.
@@ -193,6 +407,95 @@ impl<'a> Notebook<'a> {
         self.fetch_note(notebook_ship, notebook_name, comment_index)
     }
 
+    /// Fetch every revision of a note given an index `note_index`, ordered from the first
+    /// revision to the latest. `note_index` can be any valid note index (even an index of a comment on the note)
+    pub fn fetch_note_history(
+        &mut self,
+        notebook_ship: &str,
+        notebook_name: &str,
+        note_index: &str,
+    ) -> Result<Vec<NoteRevision>> {
+        // check index
+        let index = NotebookIndex::new(note_index);
+        if !index.is_valid() {
+            return Err(UrbitAPIError::InvalidNoteGraphNodeIndex(
+                note_index.to_string(),
+            ));
+        }
+
+        // get note root node
+        let node = &self.channel.graph_store().get_node(
+            notebook_ship,
+            notebook_name,
+            &index.note_root_index(),
+        )?;
+        let content_node = node
+            .children
+            .iter()
+            .find(|c| c.index_tail() == "1")
+            .ok_or_else(|| UrbitAPIError::InvalidNoteGraphNode(node.to_json().dump()))?;
+
+        let mut revisions = vec![];
+        for revision_node in &content_node.children {
+            revisions.push(NoteRevision::from_node(revision_node)?);
+        }
+        revisions.sort_by_key(|r| r.revision);
+
+        Ok(revisions)
+    }
+
+    /// Produces a textual diff between two revisions of a note (see `NoteRevision::diff`).
+    /// `note_index` can be any valid note index.
+    pub fn diff_note_revisions(
+        &mut self,
+        notebook_ship: &str,
+        notebook_name: &str,
+        note_index: &str,
+        old_revision: u64,
+        new_revision: u64,
+    ) -> Result<String> {
+        let history = self.fetch_note_history(notebook_ship, notebook_name, note_index)?;
+        let find_revision = |revision: u64| {
+            history
+                .iter()
+                .find(|r| r.revision == revision)
+                .ok_or_else(|| UrbitAPIError::NoteRevisionNotFound {
+                    note_index: note_index.to_string(),
+                    revision,
+                })
+        };
+
+        Ok(find_revision(old_revision)?.diff(find_revision(new_revision)?))
+    }
+
+    /// Publishes the title and body of an older revision of a note as the new latest revision.
+    /// `note_index` can be any valid note index.
+    /// Returns index of the newly created revision.
+    pub fn revert_note_to_revision(
+        &mut self,
+        notebook_ship: &str,
+        notebook_name: &str,
+        note_index: &str,
+        revision: u64,
+    ) -> Result<String> {
+        let history = self.fetch_note_history(notebook_ship, notebook_name, note_index)?;
+        let old_revision = history
+            .iter()
+            .find(|r| r.revision == revision)
+            .ok_or_else(|| UrbitAPIError::NoteRevisionNotFound {
+                note_index: note_index.to_string(),
+                revision,
+            })?;
+
+        self.update_note(
+            notebook_ship,
+            notebook_name,
+            note_index,
+            &old_revision.title,
+            &old_revision.contents,
+        )
+    }
+
     /// Find the index of the latest revision of a note given an index `note_index`
     /// `note_index` can be any valid note index (even an index of a comment on the note)
     pub fn fetch_note_latest_revision_index(
@@ -363,13 +666,8 @@ impl<'a> Notebook<'a> {
                 &NodeContents::new().add_text(title).add_text(body),
             ));
 
-        if let Ok(_) = gs.add_node(notebook_ship, notebook_name, &node_root) {
-            Ok(index.note_revision_index(1))
-        } else {
-            Err(UrbitAPIError::FailedToCreateNote(
-                node_root.to_json().dump(),
-            ))
-        }
+        gs.add_node(notebook_ship, notebook_name, &node_root)?;
+        Ok(index.note_revision_index(1))
     }
 
     /// Update an existing note with a new title and body.
@@ -401,11 +699,8 @@ impl<'a> Notebook<'a> {
             &NodeContents::new().add_text(title).add_text(body),
         );
 
-        if let Ok(_) = gs.add_node(notebook_ship, notebook_name, &node) {
-            Ok(node.index.clone())
-        } else {
-            Err(UrbitAPIError::FailedToCreateNote(node.to_json().dump()))
-        }
+        gs.add_node(notebook_ship, notebook_name, &node)?;
+        Ok(node.index.clone())
     }
 
     /// Add a new comment to a specific note inside of a notebook specified by `note_index`
@@ -442,13 +737,8 @@ impl<'a> Notebook<'a> {
         // assemble node tree
         let cmt_root_node = cmt_root_node.add_child(&cmt_rev_node);
         // add the nodes
-        if let Ok(_) = gs.add_node(notebook_ship, notebook_name, &cmt_root_node) {
-            Ok(cmt_rev_index.clone())
-        } else {
-            Err(UrbitAPIError::FailedToCreateComment(
-                cmt_root_node.to_json().dump(),
-            ))
-        }
+        gs.add_node(notebook_ship, notebook_name, &cmt_root_node)?;
+        Ok(cmt_rev_index.clone())
     }
 
     /// Update an existing comment on a note. `comment_index` must be a valid index for a comment
@@ -475,11 +765,89 @@ impl<'a> Notebook<'a> {
 
         let node = gs.new_node_specified(&cmt_new_index, unix_time, comment);
 
-        if let Ok(_) = gs.add_node(notebook_ship, notebook_name, &node) {
-            Ok(node.index.clone())
-        } else {
-            Err(UrbitAPIError::FailedToCreateComment(node.to_json().dump()))
+        gs.add_node(notebook_ship, notebook_name, &node)?;
+        Ok(node.index.clone())
+    }
+
+    /// Delete a note (including all of its revisions and comments) from the notebook.
+    /// `note_index` can be any valid note index (even an index of a comment on the note).
+    /// Returns the list of indices which were removed.
+    pub fn delete_note(
+        &mut self,
+        notebook_ship: &str,
+        notebook_name: &str,
+        note_index: &str,
+    ) -> Result<Vec<String>> {
+        // check index
+        let index = NotebookIndex::new(note_index);
+        if !index.is_valid() {
+            return Err(UrbitAPIError::InvalidNoteGraphNodeIndex(
+                note_index.to_string(),
+            ));
+        }
+
+        // get the note root node
+        let note_root_index = index.note_root_index();
+        let node =
+            &self
+                .channel
+                .graph_store()
+                .get_node(notebook_ship, notebook_name, &note_root_index)?;
+
+        // collect every revision, comment and comment revision of the note,
+        // with children ahead of their parents
+        let mut indices = vec![];
+        for container_node in &node.children {
+            for child_node in &container_node.children {
+                for revision_node in &child_node.children {
+                    indices.push(revision_node.index.clone());
+                }
+                indices.push(child_node.index.clone());
+            }
         }
+        indices.push(index.note_content_node_index());
+        indices.push(index.note_comments_node_index());
+        indices.push(note_root_index);
+
+        self.channel
+            .graph_store()
+            .remove_posts(notebook_ship, notebook_name, &indices)?;
+        Ok(indices)
+    }
+
+    /// Delete a comment (including all of its revisions) from a note.
+    /// `comment_index` can be the comment root node index, or the index of any revision of the comment.
+    /// Returns the list of indices which were removed.
+    pub fn delete_comment(
+        &mut self,
+        notebook_ship: &str,
+        notebook_name: &str,
+        comment_index: &str,
+    ) -> Result<Vec<String>> {
+        // check index
+        let index = NotebookIndex::new(comment_index);
+        if !index.is_valid_comment_index() {
+            return Err(UrbitAPIError::InvalidCommentGraphNodeIndex(
+                comment_index.to_string(),
+            ));
+        }
+        let comment_root_index = index.comment_root_index()?;
+
+        // get comment root node
+        let node = &self.channel.graph_store().get_node(
+            notebook_ship,
+            notebook_name,
+            &comment_root_index,
+        )?;
+
+        // collect every revision of the comment, followed by the comment root
+        let mut indices: Vec<String> = node.children.iter().map(|r| r.index.clone()).collect();
+        indices.push(comment_root_index);
+
+        self.channel
+            .graph_store()
+            .remove_posts(notebook_ship, notebook_name, &indices)?;
+        Ok(indices)
     }
 }
 
@@ -636,3 +1004,126 @@ impl<'a> NotebookIndex<'a> {
         }
     }
 }
+
+/// Produces a line based diff of two texts using their longest common subsequence of lines
+fn diff_lines(old_text: &str, new_text: &str) -> String {
+    let old_lines: Vec<&str> = old_text.lines().collect();
+    let new_lines: Vec<&str> = new_text.lines().collect();
+
+    // lcs[i][j] is the length of the longest common subsequence of old_lines[i..] and new_lines[j..]
+    let mut lcs = vec![vec![0usize; new_lines.len() + 1]; old_lines.len() + 1];
+    for i in (0..old_lines.len()).rev() {
+        for j in (0..new_lines.len()).rev() {
+            lcs[i][j] = if old_lines[i] == new_lines[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old_lines.len() && j < new_lines.len() {
+        if old_lines[i] == new_lines[j] {
+            diff.push(format!("  {}", old_lines[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(format!("- {}", old_lines[i]));
+            i += 1;
+        } else {
+            diff.push(format!("+ {}", new_lines[j]));
+            j += 1;
+        }
+    }
+    diff.extend(old_lines[i..].iter().map(|l| format!("- {}", l)));
+    diff.extend(new_lines[j..].iter().map(|l| format!("+ {}", l)));
+    diff.join("\n")
+}
+
+/// Emits YAML front-matter (including the opening and closing `---` lines)
+fn front_matter_to_string(front_matter: &Hash) -> String {
+    let mut yaml_string = String::new();
+    let mut emitter = YamlEmitter::new(&mut yaml_string);
+    // Emitting into a `String` cannot fail
+    let _ = emitter.dump(&Yaml::Hash(front_matter.clone()));
+    format!("{}\n---\n", yaml_string)
+}
+
+/// Parses a markdown document with YAML front-matter into the front-matter, the
+/// note body (with any appended comments stripped), and everything after the front-matter.
+/// Windows (CRLF) line endings are normalised to LF.
+fn parse_markdown_document(document: &str) -> Option<(Hash, String, String)> {
+    let document = document.replace("\r\n", "\n");
+    // The document must begin with the front-matter
+    let document = document.strip_prefix("---\n")?;
+    let end = document.find("\n---\n")?;
+    let yaml = YamlLoader::load_from_str(&document[..end]).ok()?;
+    let front_matter = yaml.first()?.as_hash()?.clone();
+    let rest = document[end + 5..].trim_start_matches('\n');
+    let body = rest
+        .split(MARKDOWN_COMMENTS_SEPARATOR)
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_string();
+    Some((front_matter, body, rest.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Verify that the front-matter and body of an exported note are imported unchanged,
+    // including from a file with CRLF line endings
+    fn can_round_trip_markdown_document() {
+        let note = Note {
+            title: "Title: with \"quotes\"".to_string(),
+            author: "zod".to_string(),
+            time_sent: "2021-06-03 14:05:09".to_string(),
+            contents: "# Heading\n\nSome text\n---\nMore text".to_string(),
+            comments: vec![],
+            index: "/170141184505036957608427254348286296064/1/3".to_string(),
+        };
+        let document = note.to_markdown_document();
+
+        for document in &[document.clone(), document.replace('\n', "\r\n")] {
+            let (front_matter, body, _) = parse_markdown_document(document).unwrap();
+            let field = |key: &str| front_matter[&Yaml::String(key.to_string())].clone();
+            assert_eq!(field("title").as_str(), Some(note.title.as_str()));
+            assert_eq!(field("author").as_str(), Some("~zod"));
+            assert_eq!(field("time").as_str(), Some(note.time_sent.as_str()));
+            assert_eq!(field("index").as_str(), Some(note.index.as_str()));
+            assert_eq!(field("revisions").as_i64(), Some(3));
+            assert_eq!(body, note.contents);
+        }
+        assert!(parse_markdown_document("# No front-matter").is_none());
+    }
+
+    #[test]
+    // Verify that inserted and deleted lines are marked, keeping the common lines
+    fn can_diff_inserted_and_deleted_lines() {
+        assert_eq!(diff_lines("a\nc", "a\nb\nc"), "  a\n+ b\n  c");
+        assert_eq!(diff_lines("a\nb\nc", "a\nc"), "  a\n- b\n  c");
+        assert_eq!(diff_lines("a", "a\nb"), "  a\n+ b");
+        assert_eq!(diff_lines("a\nb", "b"), "- a\n  b");
+    }
+
+    #[test]
+    // Verify that a replaced line is shown as the old line removed and the new one added
+    fn can_diff_replaced_lines() {
+        assert_eq!(diff_lines("a\nb\nc", "a\nx\nc"), "  a\n- b\n+ x\n  c");
+        assert_eq!(diff_lines("a\nb", "x\ny"), "- a\n- b\n+ x\n+ y");
+    }
+
+    #[test]
+    // Verify that empty texts are diffed as having no lines
+    fn can_diff_empty_texts() {
+        assert_eq!(diff_lines("", ""), "");
+        assert_eq!(diff_lines("", "a\nb"), "+ a\n+ b");
+        assert_eq!(diff_lines("a\nb", ""), "- a\n- b");
+        assert_eq!(diff_lines("a\nb", "a\nb"), "  a\n  b");
+    }
+}
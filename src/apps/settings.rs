@@ -0,0 +1,345 @@
+use crate::scry::ScryPath;
+use crate::subscription::SubscriptionHandle;
+use crate::{Channel, Result, UrbitAPIError};
+use json::{object, JsonValue};
+use std::collections::BTreeMap;
+
+/// A struct that provides an interface for interacting with a ship's settings-store
+pub struct Settings<'a> {
+    pub channel: &'a mut Channel,
+}
+
+/// A value of a single entry in settings-store
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettingsValue {
+    Bool(bool),
+    String(String),
+    Number(f64),
+    Array(Vec<SettingsValue>),
+}
+
+/// A bucket of entries in settings-store, keyed by entry key
+pub type SettingsBucket = BTreeMap<String, SettingsValue>;
+
+/// An event received from a settings-store subscription
+#[derive(Clone, Debug)]
+pub enum SettingsEvent {
+    /// A whole bucket was set. Contains the bucket key and the bucket.
+    PutBucket(String, SettingsBucket),
+    /// A whole bucket was deleted. Contains the bucket key.
+    DelBucket(String),
+    /// An entry was set. Contains the bucket key, entry key and value.
+    PutEntry(String, String, SettingsValue),
+    /// An entry was deleted. Contains the bucket key and entry key.
+    DelEntry(String, String),
+}
+
+impl SettingsValue {
+    /// Convert from a `JsonValue` into a `SettingsValue`
+    pub fn from_json(json: &JsonValue) -> Result<SettingsValue> {
+        if let Some(b) = json.as_bool() {
+            return Ok(SettingsValue::Bool(b));
+        }
+        if let Some(s) = json.as_str() {
+            return Ok(SettingsValue::String(s.to_string()));
+        }
+        if let Some(n) = json.as_f64() {
+            return Ok(SettingsValue::Number(n));
+        }
+        if json.is_array() {
+            let mut values = vec![];
+            for member in json.members() {
+                values.push(SettingsValue::from_json(member)?);
+            }
+            return Ok(SettingsValue::Array(values));
+        }
+        Err(UrbitAPIError::InvalidSettingsJSON(json.dump()))
+    }
+
+    /// Converts to `JsonValue`
+    pub fn to_json(&self) -> JsonValue {
+        match self {
+            SettingsValue::Bool(b) => (*b).into(),
+            SettingsValue::String(s) => s.clone().into(),
+            SettingsValue::Number(n) => (*n).into(),
+            SettingsValue::Array(values) => {
+                let values: Vec<JsonValue> = values.iter().map(|v| v.to_json()).collect();
+                values.into()
+            }
+        }
+    }
+}
+
+impl From<bool> for SettingsValue {
+    fn from(b: bool) -> Self {
+        SettingsValue::Bool(b)
+    }
+}
+
+impl From<&str> for SettingsValue {
+    fn from(s: &str) -> Self {
+        SettingsValue::String(s.to_string())
+    }
+}
+
+impl From<String> for SettingsValue {
+    fn from(s: String) -> Self {
+        SettingsValue::String(s)
+    }
+}
+
+impl From<f64> for SettingsValue {
+    fn from(n: f64) -> Self {
+        SettingsValue::Number(n)
+    }
+}
+
+impl From<Vec<SettingsValue>> for SettingsValue {
+    fn from(values: Vec<SettingsValue>) -> Self {
+        SettingsValue::Array(values)
+    }
+}
+
+impl SettingsEvent {
+    /// Convert from a `settings-event` `JsonValue` received via subscription
+    pub fn from_json(json: &JsonValue) -> Option<SettingsEvent> {
+        let event = &json["settings-event"];
+        let (key, val) = event.entries().next()?;
+        let bucket_key = val["bucket-key"].as_str()?.to_string();
+        match key {
+            "put-bucket" => Some(SettingsEvent::PutBucket(
+                bucket_key,
+                bucket_from_json(&val["bucket"]).ok()?,
+            )),
+            "del-bucket" => Some(SettingsEvent::DelBucket(bucket_key)),
+            "put-entry" => Some(SettingsEvent::PutEntry(
+                bucket_key,
+                val["entry-key"].as_str()?.to_string(),
+                SettingsValue::from_json(&val["value"]).ok()?,
+            )),
+            "del-entry" => Some(SettingsEvent::DelEntry(
+                bucket_key,
+                val["entry-key"].as_str()?.to_string(),
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Settings<'a> {
+    /// Acquire every bucket in settings-store, keyed by bucket key
+    pub fn get_all_settings(&mut self) -> Result<BTreeMap<String, SettingsBucket>> {
+        let json = self
+            .channel
+            .ship_interface
+            .scry_json("settings-store", "/all")?;
+        let mut buckets = BTreeMap::new();
+        for (bucket_key, bucket_json) in json["all"].entries() {
+            buckets.insert(bucket_key.to_string(), bucket_from_json(bucket_json)?);
+        }
+        Ok(buckets)
+    }
+
+    /// Acquire a single bucket from settings-store
+    pub fn get_bucket(&mut self, bucket_key: &str) -> Result<SettingsBucket> {
+        let path = ScryPath::new().knot("bucket").knot(bucket_key);
+        let json = self
+            .channel
+            .ship_interface
+            .scry_json("settings-store", path.as_str())?;
+        bucket_from_json(&json["bucket"])
+    }
+
+    /// Acquire a single entry from a bucket in settings-store
+    pub fn get_entry(&mut self, bucket_key: &str, entry_key: &str) -> Result<SettingsValue> {
+        let path = ScryPath::new()
+            .knot("entry")
+            .knot(bucket_key)
+            .knot(entry_key);
+        let json = self
+            .channel
+            .ship_interface
+            .scry_json("settings-store", path.as_str())?;
+        SettingsValue::from_json(&json["entry"])
+    }
+
+    /// Set an entry in a bucket in settings-store. The bucket is created if it does not exist.
+    pub fn put_entry(
+        &mut self,
+        bucket_key: &str,
+        entry_key: &str,
+        value: &SettingsValue,
+    ) -> Result<()> {
+        let prepped_json = object! {
+            "put-entry": {
+                "bucket-key": bucket_key,
+                "entry-key": entry_key,
+                "value": value.to_json()
+            }
+        };
+        self.poke_settings(&prepped_json)
+    }
+
+    /// Delete an entry from a bucket in settings-store
+    pub fn delete_entry(&mut self, bucket_key: &str, entry_key: &str) -> Result<()> {
+        let prepped_json = object! {
+            "del-entry": {
+                "bucket-key": bucket_key,
+                "entry-key": entry_key
+            }
+        };
+        self.poke_settings(&prepped_json)
+    }
+
+    /// Set a whole bucket in settings-store, replacing any existing entries
+    pub fn put_bucket(&mut self, bucket_key: &str, bucket: &SettingsBucket) -> Result<()> {
+        let mut bucket_json = object! {};
+        for (entry_key, value) in bucket {
+            bucket_json[entry_key.as_str()] = value.to_json();
+        }
+        let prepped_json = object! {
+            "put-bucket": {
+                "bucket-key": bucket_key,
+                "bucket": bucket_json
+            }
+        };
+        self.poke_settings(&prepped_json)
+    }
+
+    /// Delete a whole bucket from settings-store
+    pub fn delete_bucket(&mut self, bucket_key: &str) -> Result<()> {
+        let prepped_json = object! {
+            "del-bucket": {
+                "bucket-key": bucket_key
+            }
+        };
+        self.poke_settings(&prepped_json)
+    }
+
+    /// Subscribe to and watch for settings changes. This method returns a `SubscriptionHandle` with the
+    /// `SettingsEvent`s that take place after subscribing. Simply call `handle.try_recv()`
+    /// to read the next `SettingsEvent` if one has been received.
+    ///
+    /// Technical Note: This method actually creates a new `Channel` with your Urbit Ship, and spawns a new unix thread
+    /// locally that processes all messages on said channel. The thread stops and deletes its `Channel` once
+    /// the handle is dropped or shut down.
+    pub fn subscribe_to_settings(&mut self) -> Result<SubscriptionHandle<SettingsEvent>> {
+        // Creating a new Ship Interface Channel to pass into the new thread
+        let new_channel = self.channel.ship_interface.create_channel()?;
+        SubscriptionHandle::spawn(
+            new_channel,
+            "settings-store",
+            "/all",
+            SettingsEvent::from_json,
+        )
+    }
+
+    /// Internal method which pokes settings-store with a `settings-event`
+    fn poke_settings(&mut self, prepped_json: &JsonValue) -> Result<()> {
+        let resp = self
+            .channel
+            .poke("settings-store", "settings-event", prepped_json)?;
+
+        if resp.status().as_u16() == 204 {
+            Ok(())
+        } else {
+            Err(UrbitAPIError::from_response(resp))
+        }
+    }
+}
+
+/// Convert from a bucket `JsonValue` into a `SettingsBucket`
+fn bucket_from_json(json: &JsonValue) -> Result<SettingsBucket> {
+    if !json.is_object() {
+        return Err(UrbitAPIError::InvalidSettingsJSON(json.dump()));
+    }
+    let mut bucket = BTreeMap::new();
+    for (entry_key, value) in json.entries() {
+        bucket.insert(entry_key.to_string(), SettingsValue::from_json(value)?);
+    }
+    Ok(bucket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Verify that settings values are parsed from json and survive a round trip
+    fn can_parse_settings_value() {
+        let json = json::parse(r#"[true, "dark", 1.5, ["a", 2]]"#).unwrap();
+        let value = SettingsValue::from_json(&json).unwrap();
+        assert_eq!(
+            value,
+            SettingsValue::Array(vec![
+                true.into(),
+                "dark".into(),
+                1.5.into(),
+                vec!["a".into(), 2.0.into()].into(),
+            ])
+        );
+        assert_eq!(SettingsValue::from_json(&value.to_json()).unwrap(), value);
+
+        for invalid in &[JsonValue::Null, object! {"a": 1}, json::array![1, null]] {
+            assert!(matches!(
+                SettingsValue::from_json(invalid),
+                Err(UrbitAPIError::InvalidSettingsJSON(_))
+            ));
+        }
+    }
+
+    #[test]
+    // Verify that settings-store events are parsed, and malformed ones ignored
+    fn can_parse_settings_event() {
+        let put_bucket = object! {
+            "settings-event": {
+                "put-bucket": {"bucket-key": "display", "bucket": {"theme": "dark"}}
+            }
+        };
+        match SettingsEvent::from_json(&put_bucket) {
+            Some(SettingsEvent::PutBucket(key, bucket)) => {
+                assert_eq!(key, "display");
+                assert_eq!(bucket.get("theme"), Some(&"dark".into()));
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        let put_entry = object! {
+            "settings-event": {
+                "put-entry": {"bucket-key": "display", "entry-key": "size", "value": 12}
+            }
+        };
+        match SettingsEvent::from_json(&put_entry) {
+            Some(SettingsEvent::PutEntry(bucket_key, entry_key, value)) => {
+                assert_eq!(bucket_key, "display");
+                assert_eq!(entry_key, "size");
+                assert_eq!(value, 12.0.into());
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        let del_entry = object! {
+            "settings-event": {"del-entry": {"bucket-key": "display", "entry-key": "size"}}
+        };
+        assert!(matches!(
+            SettingsEvent::from_json(&del_entry),
+            Some(SettingsEvent::DelEntry(b, e)) if b == "display" && e == "size"
+        ));
+        let del_bucket = object! {"settings-event": {"del-bucket": {"bucket-key": "display"}}};
+        assert!(matches!(
+            SettingsEvent::from_json(&del_bucket),
+            Some(SettingsEvent::DelBucket(b)) if b == "display"
+        ));
+
+        let malformed = vec![
+            object! {"settings-event": {}},
+            object! {"settings-event": {"del-bucket": {}}},
+            object! {"settings-event": {"put-entry": {"bucket-key": "display"}}},
+            object! {"settings-event": {"put-bucket": {"bucket-key": "display", "bucket": 1}}},
+            object! {"settings-event": {"unknown": {"bucket-key": "display"}}},
+        ];
+        for json in malformed {
+            assert!(SettingsEvent::from_json(&json).is_none());
+        }
+    }
+}
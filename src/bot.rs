@@ -0,0 +1,389 @@
+use crate::error::{Result, UrbitAPIError};
+use crate::interface::ShipInterface;
+use crate::subscription::SubscriptionHandle;
+use crate::traits::messaging::{AuthoredMessage, Message, Messaging};
+use crossbeam::channel::Select;
+use regex::Regex;
+
+/// A function which handles a message received by a `Bot`.
+/// Returning `Some(Message)` makes the bot reply with said message in the same chat.
+pub type BotHandler = Box<dyn Fn(&BotMessage) -> Option<Message> + Send>;
+
+/// A function which is called with the errors a running `Bot` encounters, such as
+/// failing to send a reply. The bot keeps running after an error.
+pub type BotErrorHandler = Box<dyn FnMut(&UrbitAPIError) + Send>;
+
+/// A message received by a `Bot` along with the details of the trigger which matched it
+#[derive(Clone, Debug)]
+pub struct BotMessage {
+    /// The ship hosting the chat which the message was posted in
+    pub chat_ship: String,
+    /// The name of the chat which the message was posted in
+    pub chat_name: String,
+    /// The message itself
+    pub message: AuthoredMessage,
+    /// The name of the command (without the prefix), if a command was triggered
+    pub command: Option<String>,
+    /// The arguments passed to the command. Arguments are split at whitespace,
+    /// unless they are wrapped in double quotes. A `\` escapes the next character.
+    pub args: Vec<String>,
+    /// The capture groups of the regex, if a regex trigger was matched.
+    /// The first item is the whole match.
+    pub captures: Vec<String>,
+}
+
+/// A command registered with a `Bot`
+struct BotCommand {
+    name: String,
+    /// The ships which are allowed to use the command (all ships if `None`)
+    allowed_ships: Option<Vec<String>>,
+    handler: BotHandler,
+}
+
+/// A chat bot which watches one or more chats and responds to `!command args` commands,
+/// mentions of the bot's ship, and messages matching regexes.
+/// Messages authored by the bot's own ship are always ignored.
+///
+/// ```ignore
+/// let bot = Bot::new(ship_interface)
+///     .chat("~zod", "test-93")
+///     .command("ping", |_| Some(Message::new().add_text("pong")))
+///     .run();
+/// ```
+pub struct Bot {
+    ship_interface: ShipInterface,
+    chats: Vec<(String, String)>,
+    command_prefix: String,
+    commands: Vec<BotCommand>,
+    mention_handler: Option<BotHandler>,
+    regex_handlers: Vec<(Regex, BotHandler)>,
+    error_handler: Option<BotErrorHandler>,
+}
+
+impl Bot {
+    /// Create a new `Bot` which uses the given `ShipInterface` to read and send messages.
+    /// The default command prefix is `!`.
+    pub fn new(ship_interface: ShipInterface) -> Bot {
+        Bot {
+            ship_interface,
+            chats: vec![],
+            command_prefix: "!".to_string(),
+            commands: vec![],
+            mention_handler: None,
+            regex_handlers: vec![],
+            error_handler: None,
+        }
+    }
+
+    /// Add a chat for the bot to watch
+    pub fn chat(mut self, chat_ship: &str, chat_name: &str) -> Bot {
+        self.chats
+            .push((chat_ship.to_string(), chat_name.to_string()));
+        self
+    }
+
+    /// Set the prefix which marks a message as a command (`!` by default)
+    pub fn prefix(mut self, command_prefix: &str) -> Bot {
+        self.command_prefix = command_prefix.to_string();
+        self
+    }
+
+    /// Register a command which any ship can use
+    pub fn command<F>(mut self, name: &str, handler: F) -> Bot
+    where
+        F: Fn(&BotMessage) -> Option<Message> + Send + 'static,
+    {
+        self.commands.push(BotCommand {
+            name: name.to_string(),
+            allowed_ships: None,
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    /// Register a command which only the provided ships can use.
+    /// Uses of the command by any other ship are ignored.
+    pub fn restricted_command<F>(mut self, name: &str, allowed_ships: &[&str], handler: F) -> Bot
+    where
+        F: Fn(&BotMessage) -> Option<Message> + Send + 'static,
+    {
+        self.commands.push(BotCommand {
+            name: name.to_string(),
+            allowed_ships: Some(allowed_ships.iter().map(|s| strip_sig(s)).collect()),
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    /// Register a handler which is called when a message mentions the bot's ship
+    pub fn on_mention<F>(mut self, handler: F) -> Bot
+    where
+        F: Fn(&BotMessage) -> Option<Message> + Send + 'static,
+    {
+        self.mention_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Register a handler which is called when the text of a message matches the regex
+    pub fn on_regex<F>(mut self, regex: Regex, handler: F) -> Bot
+    where
+        F: Fn(&BotMessage) -> Option<Message> + Send + 'static,
+    {
+        self.regex_handlers.push((regex, Box::new(handler)));
+        self
+    }
+
+    /// Register a handler which is called with the errors encountered while running,
+    /// such as failing to send a reply. Errors are ignored if no handler is registered.
+    pub fn on_error<F>(mut self, handler: F) -> Bot
+    where
+        F: FnMut(&UrbitAPIError) + Send + 'static,
+    {
+        self.error_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Processes a single message posted in the given chat, calling the handlers of
+    /// every trigger which matches it. Returns the replies produced by the handlers.
+    pub fn process_message(
+        &self,
+        chat_ship: &str,
+        chat_name: &str,
+        message: &AuthoredMessage,
+    ) -> Vec<Message> {
+        let mut replies = vec![];
+        // Never respond to our own messages
+        if strip_sig(&message.author) == strip_sig(&self.ship_interface.ship_name) {
+            return replies;
+        }
+
+        let text = message_text(&message.contents);
+        let bot_message = BotMessage {
+            chat_ship: chat_ship.to_string(),
+            chat_name: chat_name.to_string(),
+            message: message.clone(),
+            command: None,
+            args: vec![],
+            captures: vec![],
+        };
+
+        // Commands
+        if let Some((name, args)) = parse_command(&text, &self.command_prefix) {
+            let author = strip_sig(&message.author);
+            for command in self.commands.iter().filter(|c| c.name == name) {
+                if let Some(allowed_ships) = &command.allowed_ships {
+                    if !allowed_ships.contains(&author) {
+                        continue;
+                    }
+                }
+                let command_message = BotMessage {
+                    command: Some(name.clone()),
+                    args: args.clone(),
+                    ..bot_message.clone()
+                };
+                replies.extend((command.handler)(&command_message));
+            }
+        }
+
+        // Mentions
+        if let Some(handler) = &self.mention_handler {
+            if mentions_ship(&message.contents, &self.ship_interface.ship_name) {
+                replies.extend(handler(&bot_message));
+            }
+        }
+
+        // Regexes
+        for (regex, handler) in &self.regex_handlers {
+            if let Some(caps) = regex.captures(&text) {
+                let captures = caps
+                    .iter()
+                    .map(|c| c.map(|m| m.as_str().to_string()).unwrap_or_default())
+                    .collect();
+                let regex_message = BotMessage {
+                    captures,
+                    ..bot_message.clone()
+                };
+                replies.extend(handler(&regex_message));
+            }
+        }
+
+        replies
+    }
+
+    /// Runs the bot, subscribing to every registered chat and responding to messages
+    /// until all of the chat subscriptions end. Blocks the current thread.
+    pub fn run(mut self) -> Result<()> {
+        let mut channel = self.ship_interface.create_channel()?;
+        let mut receivers: Vec<SubscriptionHandle<AuthoredMessage>> = vec![];
+        for (chat_ship, chat_name) in &self.chats {
+            receivers.push(channel.chat().subscribe_to_chat(chat_ship, chat_name)?);
+        }
+
+        let mut open: Vec<usize> = (0..receivers.len()).collect();
+        while !open.is_empty() {
+            // Block until a message arrives in any of the chats
+            let mut select = Select::new();
+            for &i in &open {
+                select.recv(receivers[i].receiver());
+            }
+            let operation = select.select();
+            let i = open[operation.index()];
+
+            match operation.recv(receivers[i].receiver()) {
+                Ok(message) => {
+                    let (chat_ship, chat_name) = self.chats[i].clone();
+                    let replies = self.process_message(&chat_ship, &chat_name, &message);
+                    for reply in replies {
+                        // A failed reply should not stop the bot
+                        if let Err(e) = channel.chat().send_message(&chat_ship, &chat_name, &reply)
+                        {
+                            self.report_error(&e);
+                        }
+                    }
+                }
+                // The subscription has ended, so stop watching the chat
+                Err(_) => open.retain(|&o| o != i),
+            }
+        }
+
+        for receiver in receivers {
+            receiver.shutdown();
+        }
+        channel.delete_channel();
+        Ok(())
+    }
+
+    /// Internal method which passes an error to the error handler, if any
+    fn report_error(&mut self, error: &UrbitAPIError) {
+        if let Some(handler) = self.error_handler.as_mut() {
+            handler(error);
+        }
+    }
+}
+
+/// Parses `<prefix>command arg1 "arg 2"` into the command name and list of arguments
+fn parse_command(text: &str, command_prefix: &str) -> Option<(String, Vec<String>)> {
+    let rest = text.strip_prefix(command_prefix)?;
+    // The command name must directly follow the prefix
+    if rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let mut words = vec![];
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut has_word = false;
+    let mut chars = rest.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                current.extend(chars.next());
+                has_word = true;
+            }
+            '"' => {
+                in_quotes = !in_quotes;
+                has_word = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if has_word {
+                    words.push(std::mem::take(&mut current));
+                    has_word = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_word = true;
+            }
+        }
+    }
+    if has_word {
+        words.push(current);
+    }
+
+    if words.is_empty() || words[0].is_empty() {
+        return None;
+    }
+    let name = words.remove(0);
+    Some((name, words))
+}
+
+/// Extracts the raw (unescaped) text of the message contents, joining
+/// text, urls and mentions with spaces
+fn message_text(contents: &Message) -> String {
+    let words: Vec<String> = contents
+        .content_list
+        .iter()
+        .filter_map(|c| {
+            if let Some(text) = c["text"].as_str() {
+                Some(text.trim().to_string())
+            } else if let Some(url) = c["url"].as_str() {
+                Some(url.to_string())
+            } else {
+                c["mention"].as_str().map(|m| format!("~{}", strip_sig(m)))
+            }
+        })
+        .collect();
+    words.join(" ").trim().to_string()
+}
+
+/// Checks whether the message contents contain a mention of the given ship
+fn mentions_ship(contents: &Message, ship: &str) -> bool {
+    let ship = strip_sig(ship);
+    contents
+        .content_list
+        .iter()
+        .filter_map(|c| c["mention"].as_str())
+        .any(|m| strip_sig(m) == ship)
+}
+
+/// Removes a leading `~` from a ship @p if present
+fn strip_sig(ship: &str) -> String {
+    ship.trim_start_matches('~').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses the text as a `!` command
+    fn parse(text: &str) -> Option<(String, Vec<String>)> {
+        parse_command(text, "!")
+    }
+
+    #[test]
+    // Verify that commands and their arguments are split at whitespace
+    fn can_parse_command() {
+        assert_eq!(
+            parse("!ping  a\tb "),
+            Some(("ping".to_string(), vec!["a".to_string(), "b".to_string()]))
+        );
+        assert_eq!(parse("!ping"), Some(("ping".to_string(), vec![])));
+        assert_eq!(
+            parse_command("bot, echo x", "bot, "),
+            Some(("echo".to_string(), vec!["x".to_string()]))
+        );
+        assert_eq!(parse("ping"), None);
+        assert_eq!(parse("! ping"), None);
+        assert_eq!(parse("!"), None);
+    }
+
+    #[test]
+    // Verify that quoted arguments keep their whitespace and that escapes are applied
+    fn can_parse_quoted_command() {
+        let (name, args) = parse(r#"!say "hello  world" x"y z" "" end"#).unwrap();
+        assert_eq!(name, "say");
+        assert_eq!(args, vec!["hello  world", "xy z", "", "end"]);
+
+        let (_, args) = parse(r#"!say \"quoted\" "a \"b\"" c\ d \\"#).unwrap();
+        assert_eq!(args, vec![r#""quoted""#, r#"a "b""#, "c d", r"\"]);
+    }
+
+    #[test]
+    // Verify that mentions are matched with or without the leading `~`
+    fn can_find_mentions() {
+        let message = Message::new().add_text("hi").add_mention("~zod");
+        assert!(mentions_ship(&message, "~zod"));
+        assert!(mentions_ship(&message, "zod"));
+        assert!(!mentions_ship(&message, "~bus"));
+        assert!(!mentions_ship(&Message::new().add_text("~zod"), "~zod"));
+    }
+}
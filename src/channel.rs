@@ -1,20 +1,32 @@
 use crate::apps::chat::Chat;
 use crate::apps::collections::Collection;
+use crate::apps::contacts::Contacts;
+use crate::apps::docket::Docket;
+use crate::apps::feed::Feed;
+use crate::apps::hood::Hood;
 use crate::apps::notebook::Notebook;
+use crate::apps::settings::Settings;
 use crate::error::{Result, UrbitAPIError};
+use crate::event_stream::{open_event_stream, EventReceiver};
 use crate::graphstore::GraphStore;
 use crate::interface::ShipInterface;
-use crate::subscription::{CreationID, Subscription};
-use eventsource_threaded::{EventSource, ReceiverSource};
+use crate::subscription::{
+    CreationID, EventOffer, OverflowPolicy, Subscription, SubscriptionCallbacks,
+};
+use crossbeam::channel::RecvTimeoutError;
+use eventsource_threaded::event::Event;
 use json::{object, JsonValue};
 use rand::Rng;
 use reqwest::blocking::Response;
-use reqwest::header::HeaderMap;
-use reqwest::Url;
-use std::time::SystemTime;
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// A callback which is called with the error whenever the event stream of a
+/// `Channel` fails (ex. while the ship is unreachable and it is reconnecting)
+pub type StreamErrorCallback = Box<dyn FnMut(&UrbitAPIError) + Send>;
 
 /// A Channel which is used to interact with a ship
-#[derive(Debug)]
 pub struct Channel {
     /// `ShipInterface` this channel is created from
     pub ship_interface: ShipInterface,
@@ -24,14 +36,61 @@ pub struct Channel {
     pub url: String,
     // The list of `Subscription`s for this channel
     pub subscription_list: Vec<Subscription>,
-    // / The `EventSource` for this channel which reads all of
+    // / The `EventReceiver` for this channel which reads all of
     // / the SSE events.
-    event_receiver: ReceiverSource,
+    event_receiver: EventReceiver,
+    /// An event which could not yet be added to its `Subscription` due to the
+    /// `BlockAcks` overflow policy, and thus has not been acked
+    blocked_event: Option<Event>,
+    /// The callbacks registered for each `Subscription`, keyed by creation id
+    callbacks: HashMap<CreationID, SubscriptionCallbacks>,
+    /// The highest event id which has been processed but not yet acked
+    pending_ack: Option<u64>,
+    /// The callback for errors of the event stream
+    on_stream_error: Option<StreamErrorCallback>,
     /// The current number of messages that have been sent out (which are
     /// also defined as message ids) via this `Channel`
     pub message_id_count: u64,
 }
 
+/// A builder which collects channel actions (pokes, subscribes, unsubscribes and acks)
+/// and sends them to the ship in a single PUT request via `send`.
+///
+/// ```ignore
+/// let creation_ids = channel
+///     .batch()
+///     .poke("hood", "helm-hi", &"Hello".into())
+///     .subscribe("graph-store", "/updates")
+///     .subscribe("contact-store", "/updates")
+///     .send()?;
+/// ```
+#[derive(Debug)]
+pub struct ChannelBatch<'a> {
+    channel: &'a mut Channel,
+    actions: Vec<JsonValue>,
+    /// The `Subscription`s which are added to the `Channel` once the batch is sent
+    new_subscriptions: Vec<Subscription>,
+    /// The creation ids of the `Subscription`s which are removed once the batch is sent
+    removed_subscriptions: Vec<CreationID>,
+}
+
+impl std::fmt::Debug for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Channel")
+            .field("ship_interface", &self.ship_interface)
+            .field("uid", &self.uid)
+            .field("url", &self.url)
+            .field("subscription_list", &self.subscription_list)
+            .field("event_receiver", &self.event_receiver)
+            .field("blocked_event", &self.blocked_event)
+            .field("callbacks", &self.callbacks)
+            .field("pending_ack", &self.pending_ack)
+            .field("on_stream_error", &self.on_stream_error.is_some())
+            .field("message_id_count", &self.message_id_count)
+            .finish()
+    }
+}
+
 /// Channel methods for basic functionality
 impl Channel {
     /// Create a new channel
@@ -47,7 +106,7 @@ impl Channel {
         // Channel url
         let channel_url = format!("{}/~/channel/{}", &ship_interface.url, uid);
         // Opening channel request json
-        let mut body = json::parse(r#"[]"#).unwrap();
+        let mut body = JsonValue::new_array();
         body[0] = object! {
                 "id": 1,
                 "action": "poke",
@@ -61,13 +120,8 @@ impl Channel {
         let resp = ship_interface.send_put_request(&channel_url, &body)?;
 
         if resp.status().as_u16() == 204 {
-            // Create cookie header with the ship session auth val
-            let mut headers = HeaderMap::new();
-            headers.append("cookie", ship_interface.session_auth.clone());
-            // Create the receiver
-            let url_structured =
-                Url::parse(&channel_url).map_err(|_| UrbitAPIError::FailedToCreateNewChannel)?;
-            let receiver = EventSource::new(url_structured, headers);
+            // Create the receiver, which reads events using the ship's HTTP client
+            let receiver = open_event_stream(&ship_interface, &channel_url);
 
             return Ok(Channel {
                 ship_interface: ship_interface,
@@ -75,16 +129,20 @@ impl Channel {
                 url: channel_url,
                 subscription_list: vec![],
                 event_receiver: receiver,
+                blocked_event: None,
+                callbacks: HashMap::new(),
+                pending_ack: None,
+                on_stream_error: None,
                 message_id_count: 2,
             });
         } else {
-            return Err(UrbitAPIError::FailedToCreateNewChannel);
+            Err(UrbitAPIError::from_response(resp))
         }
     }
 
     /// Sends a poke over the channel
     pub fn poke(&mut self, app: &str, mark: &str, json: &JsonValue) -> Result<Response> {
-        let mut body = json::parse(r#"[]"#).unwrap();
+        let mut body = JsonValue::new_array();
         body[0] = object! {
                 "id": self.get_and_raise_message_id_count(),
                 "action": "poke",
@@ -121,7 +179,7 @@ impl Channel {
         // Saves the message id to be reused
         let creation_id = self.get_and_raise_message_id_count();
         // Create the json body
-        let mut body = json::parse(r#"[]"#).unwrap();
+        let mut body = JsonValue::new_array();
         body[0] = object! {
                 "id": creation_id,
                 "action": "subscribe",
@@ -135,58 +193,298 @@ impl Channel {
 
         if resp.status().as_u16() == 204 {
             // Create the `Subscription`
-            let sub = Subscription {
-                channel_uid: self.uid.clone(),
-                creation_id: creation_id,
-                app: app.to_string(),
-                path: path.to_string(),
-                message_list: vec![],
-            };
+            let sub = Subscription::new(&self.uid, creation_id, app, path);
             // Add the `Subscription` to the list
             self.subscription_list.push(sub.clone());
             return Ok(creation_id);
         } else {
-            return Err(UrbitAPIError::FailedToCreateNewSubscription);
+            return Err(UrbitAPIError::from_response(resp));
         }
     }
 
+    /// Create a new `Subscription` whose message buffer holds at most `capacity`
+    /// messages, applying the `overflow_policy` once it is full. The capacity must be
+    /// at least 1.
+    pub fn create_new_subscription_with_buffer(
+        &mut self,
+        app: &str,
+        path: &str,
+        capacity: usize,
+        overflow_policy: OverflowPolicy,
+    ) -> Result<CreationID> {
+        // Check the capacity before subscribing so that no subscription is left behind
+        if capacity == 0 {
+            return Err(UrbitAPIError::InvalidBufferCapacity);
+        }
+        let creation_id = self.create_new_subscription(app, path)?;
+        if let Some(sub) = self
+            .subscription_list
+            .iter_mut()
+            .find(|s| s.creation_id == creation_id)
+        {
+            sub.set_buffer(capacity, overflow_policy)?;
+        }
+        Ok(creation_id)
+    }
+
     /// Parses SSE messages for this channel and moves them into
     /// the proper corresponding `Subscription`'s `message_list`.
+    /// Any callbacks registered for the subscriptions are called as well.
+    ///
+    /// If a `Subscription` with the `BlockAcks` overflow policy is full, the event
+    /// for it is held (without being acked) and no further events are read until
+    /// messages have been popped from said `Subscription`.
+    ///
+    /// Subscriptions which have been ended by the ship are removed from the
+    /// `subscription_list` once all of their messages have been popped.
     pub fn parse_event_messages(&mut self) {
-        let rec = &mut self.event_receiver;
+        self.subscription_list
+            .retain(|s| !s.ended || !s.message_list.is_empty());
+        // Consume all messages, starting with the event which was previously blocked
+        loop {
+            if let Some(event) = self.blocked_event.take() {
+                self.process_event(event);
+                // Stop reading if the `Subscription` still has no room for it
+                if self.blocked_event.is_some() {
+                    break;
+                }
+            }
+            match self.event_receiver.try_recv() {
+                Ok(Ok(event)) => self.process_event(event),
+                Ok(Err(e)) => self.dispatch_stream_error(&e),
+                Err(_) => break,
+            }
+        }
+        self.flush_acks();
+    }
+
+    /// Registers a callback which is called with the json of every fact of the
+    /// subscription, as the events are dispatched via `parse_event_messages`/`run`.
+    /// Facts which are passed to the callback are not kept in the `message_list`.
+    pub fn on_fact<F>(&mut self, creation_id: CreationID, callback: F) -> &mut Channel
+    where
+        F: FnMut(&JsonValue) + Send + 'static,
+    {
+        self.callbacks.entry(creation_id).or_default().on_fact = Some(Box::new(callback));
+        self
+    }
+
+    /// Registers a callback which is called once the subscription is ended by the ship
+    pub fn on_quit<F>(&mut self, creation_id: CreationID, callback: F) -> &mut Channel
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.callbacks.entry(creation_id).or_default().on_quit = Some(Box::new(callback));
+        self
+    }
+
+    /// Registers a callback which is called with the error if the ship rejects the subscription
+    pub fn on_error<F>(&mut self, creation_id: CreationID, callback: F) -> &mut Channel
+    where
+        F: FnMut(&str) + Send + 'static,
+    {
+        self.callbacks.entry(creation_id).or_default().on_error = Some(Box::new(callback));
+        self
+    }
+
+    /// Registers a callback which is called with the error whenever the event stream of
+    /// the channel fails. The stream reconnects by itself, so these errors are otherwise
+    /// ignored.
+    pub fn on_stream_error<F>(&mut self, callback: F) -> &mut Channel
+    where
+        F: FnMut(&UrbitAPIError) + Send + 'static,
+    {
+        self.on_stream_error = Some(Box::new(callback));
+        self
+    }
+
+    /// Runs an event loop which dispatches events to the registered callbacks as soon
+    /// as they arrive. Blocks the current thread until every subscription which has
+    /// callbacks registered has been ended by the ship (or rejected).
+    pub fn run(&mut self) {
+        self.run_until(None)
+    }
+
+    /// Runs an event loop which dispatches events to the registered callbacks for the given
+    /// duration, returning early if every subscription with callbacks has ended.
+    pub fn run_for(&mut self, duration: Duration) {
+        self.run_until(Some(Instant::now() + duration))
+    }
 
-        // Consume all messages
+    /// Internal method which drives the event loop until the optional deadline
+    fn run_until(&mut self, deadline: Option<Instant>) {
         loop {
-            if let Ok(event_res) = rec.try_recv() {
-                if let Err(e) = &event_res {
-                    println!("Error Event: {}", e);
+            self.parse_event_messages();
+            if self.callbacks.is_empty() {
+                break;
+            }
+            let timeout = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(timeout) if !timeout.is_zero() => timeout,
+                    _ => break,
+                },
+                None => Duration::from_secs(60),
+            };
+
+            // If an event is blocked, wait for messages to be popped elsewhere
+            if self.blocked_event.is_some() {
+                thread::sleep(timeout.min(Duration::new(0, 500000000)));
+                continue;
+            }
+            // Block until the next event arrives
+            match self.event_receiver.recv_timeout(timeout) {
+                Ok(Ok(event)) => self.process_event(event),
+                Ok(Err(e)) => self.dispatch_stream_error(&e),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        self.flush_acks();
+    }
+
+    /// Internal method which moves a single event into its `Subscription` (calling
+    /// any registered callbacks) and acks it, or holds it if it is blocked.
+    fn process_event(&mut self, event: Event) {
+        // Go through all subscriptions and find which
+        // subscription this event is for.
+        let mut offer = EventOffer::NotMatched;
+        let mut creation_id = None;
+        for sub in &mut self.subscription_list {
+            offer = sub.offer_event(&event);
+            if offer != EventOffer::NotMatched {
+                creation_id = Some(sub.creation_id);
+                break;
+            }
+        }
+
+        match offer {
+            EventOffer::Accepted => {
+                self.ack_event(&event);
+                if let Some(creation_id) = creation_id {
+                    self.dispatch_facts(creation_id);
+                }
+            }
+            // Hold the event until the `Subscription` has room for it
+            EventOffer::Blocked => self.blocked_event = Some(event),
+            EventOffer::NotMatched => {
+                if self.dispatch_ending(&event) {
+                    self.ack_event(&event);
+                }
+            }
+        }
+    }
+
+    /// Internal method which passes the buffered facts of a subscription to its
+    /// `on_fact` callback, if one is registered
+    fn dispatch_facts(&mut self, creation_id: CreationID) {
+        let on_fact = match self
+            .callbacks
+            .get_mut(&creation_id)
+            .and_then(|c| c.on_fact.as_mut())
+        {
+            Some(on_fact) => on_fact,
+            None => return,
+        };
+        if let Some(sub) = self
+            .subscription_list
+            .iter_mut()
+            .find(|s| s.creation_id == creation_id)
+        {
+            while let Some(mess) = sub.pop_message() {
+                if let Ok(json) = json::parse(&mess) {
+                    on_fact(&json);
                 }
-                if let Ok(event) = event_res {
-                    // Go through all subscriptions and find which
-                    // subscription this event is for.
-                    for sub in &mut self.subscription_list {
-                        // If adding the message succeeded (because found
-                        // correct `Subscription`) then stop.
-                        if let Some(_) = sub.add_to_message_list(&event) {
-                            // Send an ack for the processed event
-                            // Using unwrap because `add_to_message_list`
-                            // already does error checking.
-                            let eid: u64 = event.id.unwrap().parse().unwrap();
-                            let mut json = json::parse(r#"[]"#).unwrap();
-                            json[0] = object! {
-                                "id": self.message_id_count,
-                                "action": "ack",
-                                "event-id": eid,
-                            };
-                            self.message_id_count += 1;
-                            let _ack_res = self.ship_interface.send_put_request(&self.url, &json);
-                            break;
-                        }
+            }
+        }
+    }
+
+    /// Internal method which calls the `on_quit`/`on_error` callback if the event ends
+    /// one of the channel's subscriptions, and removes said `Subscription` (or marks it
+    /// as ended if it still has messages to be popped). Returns whether it did.
+    fn dispatch_ending(&mut self, event: &Event) -> bool {
+        let json = match json::parse(&event.data) {
+            Ok(json) => json,
+            Err(_) => return false,
+        };
+        let index = match json["id"].as_u64() {
+            Some(id) => match self
+                .subscription_list
+                .iter()
+                .position(|s| s.creation_id == id && !s.ended)
+            {
+                Some(index) => index,
+                None => return false,
+            },
+            None => return false,
+        };
+        let creation_id = self.subscription_list[index].creation_id;
+
+        match json["response"].as_str() {
+            Some("quit") => {
+                if let Some(mut callbacks) = self.callbacks.remove(&creation_id) {
+                    if let Some(on_quit) = callbacks.on_quit.as_mut() {
+                        on_quit();
                     }
                 }
-                continue;
             }
-            break;
+            Some("subscribe") if !json["err"].is_null() => {
+                if let Some(mut callbacks) = self.callbacks.remove(&creation_id) {
+                    if let Some(on_error) = callbacks.on_error.as_mut() {
+                        on_error(&json["err"].to_string());
+                    }
+                }
+            }
+            _ => return false,
+        }
+
+        // Keep the `Subscription` until its buffered messages have been popped
+        if self.subscription_list[index].message_list.is_empty() {
+            self.subscription_list.remove(index);
+        } else {
+            self.subscription_list[index].ended = true;
+        }
+        true
+    }
+
+    /// Internal method which passes an error of the event stream to the `on_stream_error`
+    /// callback, if one is registered
+    fn dispatch_stream_error(&mut self, error: &UrbitAPIError) {
+        if let Some(on_stream_error) = self.on_stream_error.as_mut() {
+            on_stream_error(error);
+        }
+    }
+
+    /// Internal method which queues an ack for a processed event. Queued acks are
+    /// coalesced and sent once the available events have been processed.
+    fn ack_event(&mut self, event: &Event) {
+        if let Some(eid) = event.id.as_ref().and_then(|id| id.parse::<u64>().ok()) {
+            self.pending_ack = Some(self.pending_ack.map_or(eid, |pending| pending.max(eid)));
+        }
+    }
+
+    /// Internal method which sends the queued ack, if any. Eyre clears every event
+    /// up to and including the acked event id, so a single ack covers all processed events.
+    fn flush_acks(&mut self) {
+        if let Some(eid) = self.pending_ack.take() {
+            let mut json = JsonValue::new_array();
+            json[0] = object! {
+                "id": self.message_id_count,
+                "action": "ack",
+                "event-id": eid,
+            };
+            self.message_id_count += 1;
+            let _ack_res = self.ship_interface.send_put_request(&self.url, &json);
+        }
+    }
+
+    /// Create a `ChannelBatch` which collects pokes, subscribes, unsubscribes and
+    /// acks, and then sends all of them to the ship in a single request.
+    pub fn batch(&mut self) -> ChannelBatch<'_> {
+        ChannelBatch {
+            channel: self,
+            actions: vec![],
+            new_subscriptions: vec![],
+            removed_subscriptions: vec![],
         }
     }
 
@@ -210,13 +508,14 @@ impl Channel {
             .subscription_list
             .iter()
             .position(|s| s.app == app && s.path == path)?;
-        self.subscription_list.remove(index);
+        let sub = self.subscription_list.remove(index);
+        self.callbacks.remove(&sub.creation_id);
         Some(true)
     }
 
     /// Deletes the channel
     pub fn delete_channel(self) {
-        let mut json = json::parse(r#"[]"#).unwrap();
+        let mut json = JsonValue::new_array();
         json[0] = object! {
             "id": self.message_id_count,
             "action": "delete",
@@ -261,4 +560,267 @@ impl Channel {
     pub fn collection(&mut self) -> Collection {
         Collection { channel: self }
     }
+
+    /// Create a `Contacts` struct which exposes an interface for interacting
+    /// with a ship's contact-store.
+    pub fn contacts(&mut self) -> Contacts<'_> {
+        Contacts { channel: self }
+    }
+
+    /// Create a `Settings` struct which exposes an interface for interacting
+    /// with a ship's settings-store.
+    pub fn settings(&mut self) -> Settings<'_> {
+        Settings { channel: self }
+    }
+
+    /// Create a `Hood` struct which exposes an interface for managing desks
+    /// on a ship via hood/kiln.
+    pub fn hood(&mut self) -> Hood<'_> {
+        Hood { channel: self }
+    }
+
+    /// Create a `Docket` struct which exposes an interface for reading the
+    /// metadata of installed and published apps via docket & treaty.
+    pub fn docket(&mut self) -> Docket<'_> {
+        Docket { channel: self }
+    }
+
+    /// Create a `Feed` struct which exposes an interface for interacting
+    /// with group feeds on Urbit.
+    pub fn feed(&mut self) -> Feed<'_> {
+        Feed { channel: self }
+    }
+}
+
+impl<'a> ChannelBatch<'a> {
+    /// Add a poke to the batch
+    pub fn poke(mut self, app: &str, mark: &str, json: &JsonValue) -> ChannelBatch<'a> {
+        let action = object! {
+            "id": self.channel.get_and_raise_message_id_count(),
+            "action": "poke",
+            "ship": self.channel.ship_interface.ship_name.clone(),
+            "app": app,
+            "mark": mark,
+            "json": json.clone(),
+        };
+        self.actions.push(action);
+        self
+    }
+
+    /// Add a subscription to the provided app/path to the batch. The resulting
+    /// `Subscription` is added to the `Channel` once the batch has been sent.
+    pub fn subscribe(mut self, app: &str, path: &str) -> ChannelBatch<'a> {
+        let creation_id = self.channel.get_and_raise_message_id_count();
+        let action = object! {
+            "id": creation_id,
+            "action": "subscribe",
+            "ship": self.channel.ship_interface.ship_name.clone(),
+            "app": app,
+            "path": path,
+        };
+        self.actions.push(action);
+        self.new_subscriptions
+            .push(Subscription::new(&self.channel.uid, creation_id, app, path));
+        self
+    }
+
+    /// Add an unsubscribe from the `Subscription` with the given creation id to the batch.
+    /// The `Subscription` is removed from the `Channel` once the batch has been sent.
+    pub fn unsubscribe(mut self, creation_id: CreationID) -> ChannelBatch<'a> {
+        let action = object! {
+            "id": self.channel.get_and_raise_message_id_count(),
+            "action": "unsubscribe",
+            "subscription": creation_id,
+        };
+        self.actions.push(action);
+        self.removed_subscriptions.push(creation_id);
+        self
+    }
+
+    /// Add an ack of the given SSE event id to the batch
+    pub fn ack(mut self, event_id: u64) -> ChannelBatch<'a> {
+        let action = object! {
+            "id": self.channel.get_and_raise_message_id_count(),
+            "action": "ack",
+            "event-id": event_id,
+        };
+        self.actions.push(action);
+        self
+    }
+
+    /// The number of actions in the batch
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    /// Whether the batch has no actions
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// Sends every action in the batch to the ship in a single request. Returns the
+    /// creation ids of the new subscriptions, in the order they were added to the batch.
+    pub fn send(self) -> Result<Vec<CreationID>> {
+        let ChannelBatch {
+            channel,
+            actions,
+            new_subscriptions,
+            removed_subscriptions,
+        } = self;
+        let creation_ids = new_subscriptions.iter().map(|s| s.creation_id).collect();
+        if actions.is_empty() {
+            return Ok(creation_ids);
+        }
+
+        let body = JsonValue::Array(actions);
+        let resp = channel
+            .ship_interface
+            .send_put_request(&channel.url, &body)?;
+        if resp.status().as_u16() != 204 {
+            return Err(UrbitAPIError::from_response(resp));
+        }
+
+        channel
+            .subscription_list
+            .retain(|s| !removed_subscriptions.contains(&s.creation_id));
+        for creation_id in &removed_subscriptions {
+            channel.callbacks.remove(creation_id);
+        }
+        channel.subscription_list.extend(new_subscriptions);
+        Ok(creation_ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_stream::test_event_stream;
+    use crossbeam::channel::Sender;
+    use std::sync::{Arc, Mutex};
+
+    /// Creates a `Channel` with the given subscriptions which reads the events sent
+    /// to the returned `Sender` instead of the event stream of a ship
+    fn test_channel(creation_ids: &[CreationID]) -> (Sender<Result<Event>>, Channel) {
+        let (sender, event_receiver) = test_event_stream();
+        let ship_interface = ShipInterface::offline();
+        let subscription_list = creation_ids
+            .iter()
+            .map(|&id| Subscription::new("1", id, "app", &format!("/path/{}", id)))
+            .collect();
+        let channel = Channel {
+            url: format!("{}/~/channel/1", ship_interface.url),
+            ship_interface,
+            uid: "1".to_string(),
+            subscription_list,
+            event_receiver,
+            blocked_event: None,
+            callbacks: HashMap::new(),
+            pending_ack: None,
+            on_stream_error: None,
+            message_id_count: 10,
+        };
+        (sender, channel)
+    }
+
+    /// Sends a raw SSE event with the given event id and json data
+    fn send_event(sender: &Sender<Result<Event>>, event_id: u64, data: &str) {
+        let mut event = Event::new();
+        event.id = Some(event_id.to_string());
+        event.data = data.to_string();
+        sender.send(Ok(event)).unwrap();
+    }
+
+    #[test]
+    // Verify that facts, quits and rejected subscribes are dispatched to their callbacks
+    fn can_dispatch_events() {
+        let (sender, mut channel) = test_channel(&[2, 3]);
+        let facts = Arc::new(Mutex::new(vec![]));
+        let quit = Arc::new(Mutex::new(false));
+        let error = Arc::new(Mutex::new(String::new()));
+        let (f, q, e) = (facts.clone(), quit.clone(), error.clone());
+        channel
+            .on_fact(2, move |json| f.lock().unwrap().push(json.dump()))
+            .on_quit(2, move || *q.lock().unwrap() = true)
+            .on_error(3, move |err| *e.lock().unwrap() = err.to_string());
+
+        send_event(&sender, 1, r#"{"id":2,"response":"diff","json":{"a":1}}"#);
+        send_event(&sender, 2, r#"{"id":4,"response":"poke","ok":"ok"}"#);
+        send_event(
+            &sender,
+            3,
+            r#"{"id":3,"response":"subscribe","err":"nope"}"#,
+        );
+        send_event(&sender, 4, r#"{"id":2,"response":"quit"}"#);
+        channel.parse_event_messages();
+
+        assert_eq!(*facts.lock().unwrap(), vec![r#"{"a":1}"#]);
+        assert!(*quit.lock().unwrap());
+        assert!(error.lock().unwrap().contains("nope"));
+        // Both subscriptions have ended, so they are removed along with their callbacks
+        assert!(channel.subscription_list.is_empty());
+        assert!(channel.callbacks.is_empty());
+        assert_eq!(channel.pending_ack, None);
+    }
+
+    #[test]
+    // Verify that an ended subscription is kept until its messages have been popped
+    fn keeps_ended_subscription_until_popped() {
+        let (sender, mut channel) = test_channel(&[2]);
+        send_event(&sender, 1, r#"{"id":2,"response":"diff","json":"last"}"#);
+        send_event(&sender, 2, r#"{"id":2,"response":"quit"}"#);
+        channel.parse_event_messages();
+
+        let sub = channel.find_subscription("app", "/path/2").unwrap();
+        assert!(sub.ended);
+        assert_eq!(sub.pop_message().as_deref(), Some(r#""last""#));
+        channel.parse_event_messages();
+        assert!(channel.subscription_list.is_empty());
+    }
+
+    #[test]
+    // Verify that events after a blocked event are read once it has room, and that
+    // errors of the event stream are passed to the callback
+    fn can_resume_blocked_events() {
+        let (sender, mut channel) = test_channel(&[2]);
+        channel.subscription_list[0]
+            .set_buffer(1, OverflowPolicy::BlockAcks)
+            .unwrap();
+        let errors = Arc::new(Mutex::new(0));
+        let e = errors.clone();
+        channel.on_stream_error(move |_| *e.lock().unwrap() += 1);
+
+        for i in 1..=3 {
+            let data = format!(r#"{{"id":2,"response":"diff","json":{}}}"#, i);
+            send_event(&sender, i, &data);
+        }
+        sender
+            .send(Err(UrbitAPIError::Other("dropped".to_string())))
+            .unwrap();
+
+        for i in 1..=3 {
+            channel.parse_event_messages();
+            let sub = &mut channel.subscription_list[0];
+            assert_eq!(sub.message_list, vec![i.to_string()]);
+            sub.pop_message();
+        }
+        assert!(channel.blocked_event.is_none());
+        assert_eq!(*errors.lock().unwrap(), 1);
+    }
+
+    #[test]
+    // Verify that the event loop returns once the deadline has passed, without pending acks
+    fn run_for_flushes_acks() {
+        let (sender, mut channel) = test_channel(&[2]);
+        let facts = Arc::new(Mutex::new(0));
+        let f = facts.clone();
+        channel.on_fact(2, move |_| *f.lock().unwrap() += 1);
+
+        send_event(&sender, 7, r#"{"id":2,"response":"diff","json":null}"#);
+        send_event(&sender, 8, r#"{"id":2,"response":"diff","json":{"a":1}}"#);
+        channel.run_for(Duration::from_millis(50));
+
+        assert_eq!(*facts.lock().unwrap(), 1);
+        assert_eq!(channel.pending_ack, None);
+        assert!(channel.message_id_count > 10);
+    }
 }
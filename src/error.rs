@@ -1,16 +1,31 @@
+use reqwest::blocking::Response;
 use reqwest::Error as ReqError;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, UrbitAPIError>;
 
+/// The HTTP statuses which indicate a transient failure, after which the same
+/// request may succeed if retried
+pub(crate) const RETRYABLE_STATUSES: [u16; 5] = [408, 429, 502, 503, 504];
+
 #[derive(Error, Debug)]
 pub enum UrbitAPIError {
     #[error("Failed logging in to the ship given the provided url and code.")]
     FailedToLogin,
+    #[error("Failed to determine the name of the ship at {0}")]
+    FailedToDiscoverShipName(String),
+    #[error("The session is invalid or has expired {0}")]
+    InvalidSession(String),
+    #[error("Failed to save the session to {0}")]
+    FailedToSaveSession(String),
+    #[error("Failed to load a session from {0}")]
+    FailedToLoadSession(String),
     #[error("Failed to create a new channel.")]
     FailedToCreateNewChannel,
     #[error("Failed to create a new subscription.")]
     FailedToCreateNewSubscription,
+    #[error("A subscription message buffer must have a capacity of at least one message.")]
+    InvalidBufferCapacity,
     #[error("Failed to fetch Graph Store keys.")]
     FailedToFetchKeys,
     #[error("Failed to fetch Graph Store tags.")]
@@ -49,14 +64,107 @@ pub enum UrbitAPIError {
     InvalidLinkGraphNode(String),
     #[error("The following graph node index is not a valid Notebook Note node index {0}")]
     InvalidNoteGraphNodeIndex(String),
+    #[error("The Notebook Note {note_index} has no revision {revision}")]
+    NoteRevisionNotFound {
+        /// The index of the note
+        note_index: String,
+        /// The revision which does not exist
+        revision: u64,
+    },
     #[error("Failed to create a Notebook Note from these nodes {0}")]
     FailedToCreateNote(String),
     #[error("Failed to create a Notebook Comment from these nodes {0}")]
     FailedToCreateComment(String),
     #[error("The following graph node index is not a valid Notebook Comment node index {0}")]
     InvalidCommentGraphNodeIndex(String),
+    #[error("The following graph node index is not a valid Feed post node index {0}")]
+    InvalidPostGraphNodeIndex(String),
+    #[error("Failed to export Notebook to {0}")]
+    FailedToExportNotebook(String),
+    #[error("Failed to import Notebook from {0}")]
+    FailedToImportNotebook(String),
+    #[error("The following json is not a valid contact {0}")]
+    InvalidContactJSON(String),
+    #[error("The following json is not a valid settings-store value {0}")]
+    InvalidSettingsJSON(String),
+    #[error("Failed to fetch kiln state from hood at path {0}")]
+    FailedToFetchKilnState(String),
+    #[error("The following json is not a valid kiln vat {0}")]
+    InvalidVatJSON(String),
+    #[error("Failed to fetch app metadata at path {0}")]
+    FailedToFetchDocket(String),
+    #[error("The following json is not valid docket metadata {0}")]
+    InvalidDocketJSON(String),
+    #[error("The following is not a valid cron schedule {0}")]
+    InvalidCronSchedule(String),
+    #[error("Failed to load scheduled messages from {0}")]
+    FailedToLoadSchedule(String),
+    #[error("Failed to persist scheduled messages to {0}")]
+    FailedToPersistSchedule(String),
+    #[error("Failed to decode the result of the scry {0}")]
+    FailedToDecodeScry(String),
+    #[error("Request to {endpoint} failed with status {status}: {body}")]
+    HttpError {
+        /// The HTTP status code of the response
+        status: u16,
+        /// The path of the endpoint which the request was made to
+        endpoint: String,
+        /// The body of the response, often a Hoon tang for failed scries and threads
+        body: String,
+    },
+    #[error("Thread {thread} failed: {tang}")]
+    ThreadFailed {
+        /// The name of the thread
+        thread: String,
+        /// The tang (error trace) of the failed thread
+        tang: String,
+    },
     #[error("{0}")]
     Other(String),
     #[error(transparent)]
     ReqwestError(#[from] ReqError),
 }
+
+/// Whether the request failed because the connection to the ship failed while the
+/// request was being sent or the response read (ex. the connection being reset)
+pub(crate) fn is_dropped_connection(error: &ReqError) -> bool {
+    if !error.is_request() && !error.is_body() {
+        return false;
+    }
+    // Look for an io error in the chain of causes
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        if cause.is::<std::io::Error>() {
+            return true;
+        }
+        source = cause.source();
+    }
+    false
+}
+
+impl UrbitAPIError {
+    /// Create an `HttpError` from a response which had an unexpected status,
+    /// consuming the response to read the error body sent by the ship.
+    pub fn from_response(resp: Response) -> UrbitAPIError {
+        let status = resp.status().as_u16();
+        let endpoint = resp.url().path().to_string();
+        let body = resp.text().unwrap_or_default();
+        UrbitAPIError::HttpError {
+            status,
+            endpoint,
+            body,
+        }
+    }
+
+    /// Whether the failure is likely to be transient, meaning that the same request may
+    /// succeed if retried (ex. timeouts, dropped connections or the ship being unavailable).
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            UrbitAPIError::HttpError { status, .. } => RETRYABLE_STATUSES.contains(status),
+            UrbitAPIError::ReqwestError(e) => {
+                e.is_timeout() || e.is_connect() || is_dropped_connection(e)
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use crate::error::{Result, UrbitAPIError};
+use crate::interface::ShipInterface;
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use eventsource_threaded::event::{parse_event_line, Event, ParseResult};
+use reqwest::header::{HeaderValue, ACCEPT, COOKIE};
+use std::io::{BufRead, BufReader};
+use std::ops::Deref;
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::Duration;
+
+/// The default delay before reconnecting to the event stream
+static DEFAULT_RECONNECT_MILLIS: u64 = 5000;
+/// The timeout of a single event stream request, which overrides the (much shorter)
+/// timeout of the client as the stream is expected to stay open indefinitely
+static EVENT_STREAM_TIMEOUT_SECS: u64 = 24 * 60 * 60;
+
+/// The `Receiver` which the SSE events of a channel are sent to. Dropping it stops
+/// the thread reading the event stream (along with its connection to the ship).
+#[derive(Debug)]
+pub struct EventReceiver {
+    receiver: Receiver<Result<Event>>,
+    /// Kept alive for as long as the receiver is, so that the reader thread
+    /// can tell whether anyone is still listening
+    _listening: Arc<()>,
+}
+
+impl Deref for EventReceiver {
+    type Target = Receiver<Result<Event>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.receiver
+    }
+}
+
+/// The sending end of an `EventReceiver`, used by the reader thread
+struct EventSender {
+    sender: Sender<Result<Event>>,
+    listening: Weak<()>,
+}
+
+impl EventSender {
+    /// Whether the `EventReceiver` has not been dropped yet
+    fn is_listening(&self) -> bool {
+        self.listening.strong_count() > 0
+    }
+
+    /// Sends the event, returning whether the `EventReceiver` is still listening
+    fn send(&self, event: Result<Event>) -> bool {
+        self.is_listening() && self.sender.send(event).is_ok()
+    }
+}
+
+/// Spawns a thread which reads the SSE event stream of the channel at `channel_url`
+/// using the HTTP client and session of the `ShipInterface`, and sends every event
+/// to the returned `Receiver`. The stream is reconnected (resuming from the last
+/// received event) if it drops. The thread stops once the `Receiver` is dropped or
+/// the channel no longer exists on the ship.
+pub(crate) fn open_event_stream(
+    ship_interface: &ShipInterface,
+    channel_url: &str,
+) -> EventReceiver {
+    let (s, r) = unbounded();
+    let listening = Arc::new(());
+    let sender = EventSender {
+        sender: s,
+        listening: Arc::downgrade(&listening),
+    };
+    let ship_interface = ship_interface.clone();
+    let channel_url = channel_url.to_string();
+
+    thread::spawn(move || {
+        let mut last_event_id: Option<String> = None;
+        let mut reconnect_delay = Duration::from_millis(DEFAULT_RECONNECT_MILLIS);
+        while sender.is_listening() {
+            match read_event_stream(
+                &ship_interface,
+                &channel_url,
+                &sender,
+                &mut last_event_id,
+                &mut reconnect_delay,
+            ) {
+                // The receiver was dropped or the channel was deleted
+                StreamEnd::Closed => break,
+                StreamEnd::Dropped(Some(e)) => {
+                    if !sender.send(Err(e)) {
+                        break;
+                    }
+                }
+                StreamEnd::Dropped(None) => (),
+            }
+            thread::sleep(reconnect_delay);
+        }
+    });
+
+    EventReceiver {
+        receiver: r,
+        _listening: listening,
+    }
+}
+
+/// Creates an `EventReceiver` along with the `Sender` of its events, used to feed
+/// events to a `Channel` without a ship
+#[cfg(test)]
+pub(crate) fn test_event_stream() -> (Sender<Result<Event>>, EventReceiver) {
+    let (s, r) = unbounded();
+    let receiver = EventReceiver {
+        receiver: r,
+        _listening: Arc::new(()),
+    };
+    (s, receiver)
+}
+
+/// The reason reading the event stream stopped
+#[derive(Debug)]
+enum StreamEnd {
+    /// The stream should not be reconnected
+    Closed,
+    /// The stream dropped (along with the error, if any) and should be reconnected
+    Dropped(Option<UrbitAPIError>),
+}
+
+/// Connects to the event stream and forwards events until it ends
+fn read_event_stream(
+    ship_interface: &ShipInterface,
+    channel_url: &str,
+    sender: &EventSender,
+    last_event_id: &mut Option<String>,
+    reconnect_delay: &mut Duration,
+) -> StreamEnd {
+    let mut request = ship_interface
+        .client()
+        .get(channel_url)
+        .header(COOKIE, ship_interface.session_auth())
+        .header(ACCEPT, "text/event-stream")
+        .timeout(Duration::from_secs(EVENT_STREAM_TIMEOUT_SECS));
+    if let Some(id) = last_event_id.as_ref() {
+        if let Ok(id) = HeaderValue::from_str(id) {
+            request = request.header("Last-Event-ID", id);
+        }
+    }
+
+    let resp = match request.send() {
+        Ok(resp) => resp,
+        Err(e) => return StreamEnd::Dropped(Some(e.into())),
+    };
+    match resp.status().as_u16() {
+        200 => (),
+        // The channel no longer exists
+        404 => return StreamEnd::Closed,
+        _ => return StreamEnd::Dropped(Some(UrbitAPIError::from_response(resp))),
+    }
+
+    read_events(
+        BufReader::new(resp),
+        channel_url,
+        sender,
+        last_event_id,
+        reconnect_delay,
+    )
+}
+
+/// Parses the events of an event stream line by line and forwards them until it ends
+fn read_events<R: BufRead>(
+    mut reader: R,
+    channel_url: &str,
+    sender: &EventSender,
+    last_event_id: &mut Option<String>,
+    reconnect_delay: &mut Duration,
+) -> StreamEnd {
+    let mut event = Event::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            // The stream was closed by the ship
+            Ok(0) => return StreamEnd::Dropped(None),
+            Ok(_) => match parse_event_line(&line, &mut event) {
+                ParseResult::Next => (),
+                ParseResult::Dispatch => {
+                    let dispatched = std::mem::replace(&mut event, Event::new());
+                    // Skip keep-alive comments, which dispatch empty events, however
+                    // stop if nobody is listening anymore
+                    if dispatched.is_empty() {
+                        if !sender.is_listening() {
+                            return StreamEnd::Closed;
+                        }
+                        continue;
+                    }
+                    if dispatched.id.is_some() {
+                        *last_event_id = dispatched.id.clone();
+                    }
+                    if !sender.send(Ok(dispatched)) {
+                        return StreamEnd::Closed;
+                    }
+                }
+                ParseResult::SetRetry(retry) => *reconnect_delay = retry,
+            },
+            Err(e) => {
+                return StreamEnd::Dropped(Some(UrbitAPIError::Other(format!(
+                    "Event stream of {} dropped: {}",
+                    channel_url, e
+                ))))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Reads the stream, returning how it ended and the events which were sent
+    fn read_test_stream(
+        stream: &str,
+        listening: bool,
+    ) -> (StreamEnd, Vec<Event>, Option<String>, Duration) {
+        let (s, r) = unbounded();
+        let listener = Arc::new(());
+        let sender = EventSender {
+            sender: s,
+            listening: Arc::downgrade(&listener),
+        };
+        if !listening {
+            drop(listener);
+        }
+        let mut last_event_id = None;
+        let mut reconnect_delay = Duration::from_millis(DEFAULT_RECONNECT_MILLIS);
+        let end = read_events(
+            Cursor::new(stream.to_string()),
+            "test",
+            &sender,
+            &mut last_event_id,
+            &mut reconnect_delay,
+        );
+        let events = r.try_iter().filter_map(|e| e.ok()).collect();
+        (end, events, last_event_id, reconnect_delay)
+    }
+
+    #[test]
+    // Verify that events with ids and multi-line data are parsed, and comments skipped
+    fn can_parse_event_stream() {
+        let stream = ":keep-alive\n\nid: 1\ndata: {\"a\":\ndata: 1}\n\n: comment\nid: 2\ndata: x\n\nretry: 100\n";
+        let (end, events, last_event_id, reconnect_delay) = read_test_stream(stream, true);
+        assert!(matches!(end, StreamEnd::Dropped(None)));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].id.as_deref(), Some("1"));
+        assert_eq!(events[0].data, "{\"a\":\n1}\n");
+        assert_eq!(events[1].data, "x\n");
+        assert_eq!(last_event_id.as_deref(), Some("2"));
+        assert_eq!(reconnect_delay, Duration::from_millis(100));
+    }
+
+    #[test]
+    // Verify that CRLF line endings are handled
+    fn can_parse_crlf_event_stream() {
+        let stream = "id: 7\r\ndata: hello\r\ndata: world\r\n\r\n";
+        let (_, events, last_event_id, _) = read_test_stream(stream, true);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "hello\nworld\n");
+        assert_eq!(last_event_id.as_deref(), Some("7"));
+    }
+
+    #[test]
+    // Verify that reading stops on keep-alives once the receiver has been dropped
+    fn stops_when_not_listening() {
+        let (end, events, _, _) = read_test_stream(":keep-alive\n\nid: 1\ndata: x\n\n", false);
+        assert!(matches!(end, StreamEnd::Closed));
+        assert!(events.is_empty());
+    }
+}
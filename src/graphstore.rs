@@ -8,6 +8,7 @@ pub enum Module {
     Chat,
     Notebook,
     Collection,
+    Post,
     Null,
 }
 
@@ -80,9 +81,7 @@ impl<'a> GraphStore<'a> {
         if resp.status().as_u16() == 204 {
             Ok(())
         } else {
-            return Err(UrbitAPIError::FailedToAddNodesToGraphStore(
-                resource_name.to_string(),
-            ));
+            Err(UrbitAPIError::from_response(resp))
         }
     }
 
@@ -113,9 +112,7 @@ impl<'a> GraphStore<'a> {
         if resp.status().as_u16() == 200 {
             Ok(())
         } else {
-            return Err(UrbitAPIError::FailedToAddNodesToGraphStore(
-                resource_name.to_string(),
-            ));
+            Err(UrbitAPIError::from_response(resp))
         }
     }
 
@@ -141,9 +138,37 @@ impl<'a> GraphStore<'a> {
         if resp.status().as_u16() == 204 {
             Ok(())
         } else {
-            return Err(UrbitAPIError::FailedToRemoveNodesFromGraphStore(
-                resource_name.to_string(),
-            ));
+            Err(UrbitAPIError::from_response(resp))
+        }
+    }
+
+    /// Remove posts from Graph Store using the provided list of indices.
+    /// Unlike `remove_nodes` this uses the `remove-posts` action of `graph-update-3`,
+    /// thereby removing every index in a single poke.
+    pub fn remove_posts(
+        &mut self,
+        resource_ship: &str,
+        resource_name: &str,
+        indices: &[String],
+    ) -> Result<()> {
+        let prepped_json = object! {
+            "remove-posts": {
+                "resource": {
+                    "ship": resource_ship,
+                    "name": resource_name
+                },
+            "indices": indices.to_vec()
+            }
+        };
+
+        let resp = self
+            .channel
+            .poke("graph-push-hook", "graph-update-3", &prepped_json)?;
+
+        if resp.status().as_u16() == 204 {
+            Ok(())
+        } else {
+            Err(UrbitAPIError::from_response(resp))
         }
     }
 
@@ -156,24 +181,11 @@ impl<'a> GraphStore<'a> {
     ) -> Result<Node> {
         let path_nodes = index_dec_to_ud(node_index);
         let path = format!("/node/{}/{}{}", resource_ship, resource_name, &path_nodes);
-        let res = self
+        let node_json = self
             .channel
             .ship_interface
-            .scry("graph-store", &path, "json")?;
-
-        // If successfully acquired node json
-        if res.status().as_u16() == 200 {
-            if let Ok(body) = res.text() {
-                if let Ok(node_json) = json::parse(&body) {
-                    return Node::from_graph_update_json(&node_json);
-                }
-            }
-        }
-        // Else return error
-        Err(UrbitAPIError::FailedToGetGraphNode(format!(
-            "/{}/{}/{}",
-            resource_ship, resource_name, node_index
-        )))
+            .scry_json("graph-store", &path)?;
+        Node::from_graph_update_json(&node_json)
     }
 
     /// Acquire a subset of children of a node from Graph Store by specifying the start and end indices
@@ -190,21 +202,7 @@ impl<'a> GraphStore<'a> {
             "/node-children-subset/{}/{}/{}/{}/{}",
             resource_ship, resource_name, node_index, end_index, start_index
         );
-        let res = self
-            .channel
-            .ship_interface
-            .scry("graph-store", &path, "json")?;
-
-        // If successfully acquired node json
-        if res.status().as_u16() == 200 {
-            if let Ok(body) = res.text() {
-                if let Ok(graph_json) = json::parse(&body) {
-                    return Graph::from_json(graph_json);
-                }
-            }
-        }
-        // Else return error
-        Err(UrbitAPIError::FailedToGetGraph(resource_name.to_string()))
+        self.channel.ship_interface.scry_typed("graph-store", &path)
     }
 
     /// Create a new graph on the connected Urbit ship that is managed
@@ -237,18 +235,17 @@ impl<'a> GraphStore<'a> {
             }
         };
 
-        let resp = self
-            .channel
-            .ship_interface
-            .spider("graph-view-action", "json", "graph-create", &create_req)
-            .unwrap();
+        let resp = self.channel.ship_interface.spider(
+            "graph-view-action",
+            "json",
+            "graph-create",
+            &create_req,
+        )?;
 
         if resp.status().as_u16() == 200 {
             Ok(())
         } else {
-            Err(UrbitAPIError::FailedToCreateGraphInShip(
-                graph_resource_name.to_string(),
-            ))
+            Err(UrbitAPIError::from_response(resp))
         }
     }
 
@@ -281,18 +278,17 @@ impl<'a> GraphStore<'a> {
             }
         };
 
-        let resp = self
-            .channel
-            .ship_interface
-            .spider("graph-view-action", "json", "graph-create", &create_req)
-            .unwrap();
+        let resp = self.channel.ship_interface.spider(
+            "graph-view-action",
+            "json",
+            "graph-create",
+            &create_req,
+        )?;
 
         if resp.status().as_u16() == 200 {
             Ok(())
         } else {
-            Err(UrbitAPIError::FailedToCreateGraphInShip(
-                graph_resource_name.to_string(),
-            ))
+            Err(UrbitAPIError::from_response(resp))
         }
     }
 
@@ -328,21 +324,7 @@ impl<'a> GraphStore<'a> {
     /// Acquire a graph from Graph Store
     pub fn get_graph(&mut self, resource_ship: &str, resource_name: &str) -> Result<Graph> {
         let path = format!("/graph/{}/{}", resource_ship, resource_name);
-        let res = self
-            .channel
-            .ship_interface
-            .scry("graph-store", &path, "json")?;
-
-        // If successfully acquired graph json
-        if res.status().as_u16() == 200 {
-            if let Ok(body) = res.text() {
-                if let Ok(graph_json) = json::parse(&body) {
-                    return Graph::from_json(graph_json);
-                }
-            }
-        }
-        // Else return error
-        Err(UrbitAPIError::FailedToGetGraph(resource_name.to_string()))
+        self.channel.ship_interface.scry_typed("graph-store", &path)
     }
 
     /// Acquire a subset of a graph from Graph Store by specifying the start and end indices
@@ -358,21 +340,7 @@ impl<'a> GraphStore<'a> {
             "/graph-subset/{}/{}/{}/{}",
             resource_ship, resource_name, end_index, start_index
         );
-        let res = self
-            .channel
-            .ship_interface
-            .scry("graph-store", &path, "json")?;
-
-        // If successfully acquired graph json
-        if res.status().as_u16() == 200 {
-            if let Ok(body) = res.text() {
-                if let Ok(graph_json) = json::parse(&body) {
-                    return Graph::from_json(graph_json);
-                }
-            }
-        }
-        // Else return error
-        Err(UrbitAPIError::FailedToGetGraph(resource_name.to_string()))
+        self.channel.ship_interface.scry_typed("graph-store", &path)
     }
 
     /// Delete graph from Graph Store
@@ -392,9 +360,7 @@ impl<'a> GraphStore<'a> {
         if resp.status().as_u16() == 204 {
             Ok(())
         } else {
-            return Err(UrbitAPIError::FailedToRemoveGraphFromGraphStore(
-                resource_name.to_string(),
-            ));
+            Err(UrbitAPIError::from_response(resp))
         }
     }
 
@@ -415,46 +381,28 @@ impl<'a> GraphStore<'a> {
         if resp.status().as_u16() == 204 {
             Ok(())
         } else {
-            return Err(UrbitAPIError::FailedToRemoveGraphFromGraphStore(
-                resource_name.to_string(),
-            ));
+            Err(UrbitAPIError::from_response(resp))
         }
     }
 
     /// Archive a graph in Graph Store
     pub fn archive_graph(&mut self, resource_ship: &str, resource_name: &str) -> Result<String> {
         let path = format!("/archive/{}/{}", resource_ship, resource_name);
-        let res = self
+        Ok(self
             .channel
             .ship_interface
-            .scry("graph-store", &path, "json")?;
-
-        if res.status().as_u16() == 200 {
-            if let Ok(body) = res.text() {
-                return Ok(body);
-            }
-        }
-        return Err(UrbitAPIError::FailedToArchiveGraph(
-            resource_name.to_string(),
-        ));
+            .scry_json("graph-store", &path)?
+            .dump())
     }
 
     /// Unarchive a graph in Graph Store
     pub fn unarchive_graph(&mut self, resource_ship: &str, resource_name: &str) -> Result<String> {
         let path = format!("/unarchive/{}/{}", resource_ship, resource_name);
-        let res = self
+        Ok(self
             .channel
             .ship_interface
-            .scry("graph-store", &path, "json")?;
-
-        if res.status().as_u16() == 200 {
-            if let Ok(body) = res.text() {
-                return Ok(body);
-            }
-        }
-        return Err(UrbitAPIError::FailedToArchiveGraph(
-            resource_name.to_string(),
-        ));
+            .scry_json("graph-store", &path)?
+            .dump())
     }
 
     /// Add a tag to a graph
@@ -474,7 +422,7 @@ impl<'a> GraphStore<'a> {
         if resp.status().as_u16() == 204 {
             Ok(())
         } else {
-            return Err(UrbitAPIError::FailedToAddTag(resource_name.to_string()));
+            Err(UrbitAPIError::from_response(resp))
         }
     }
 
@@ -500,107 +448,58 @@ impl<'a> GraphStore<'a> {
         if resp.status().as_u16() == 204 {
             Ok(())
         } else {
-            return Err(UrbitAPIError::FailedToRemoveTag(resource_name.to_string()));
+            Err(UrbitAPIError::from_response(resp))
         }
     }
 
     /// Performs a scry to get all keys
     pub fn get_keys(&mut self) -> Result<Vec<JsonValue>> {
-        let resp = self
+        let json = self
             .channel
             .ship_interface
-            .scry("graph-store", "/keys", "json")?;
-
-        if resp.status().as_u16() == 200 {
-            let json_text = resp.text()?;
-            if let Ok(json) = json::parse(&json_text) {
-                let keys = json["graph-update"]["keys"].clone();
-                let mut keys_list = vec![];
-                for key in keys.members() {
-                    keys_list.push(key.clone())
-                }
-                return Ok(keys_list);
-            }
-        }
-        return Err(UrbitAPIError::FailedToFetchKeys);
+            .scry_json("graph-store", "/keys")?;
+        Ok(json["graph-update"]["keys"].members().cloned().collect())
     }
 
     /// Performs a scry to get all tags
     pub fn get_tags(&mut self) -> Result<Vec<JsonValue>> {
-        let resp = self
+        let json = self
             .channel
             .ship_interface
-            .scry("graph-store", "/tags", "json")?;
-
-        if resp.status().as_u16() == 200 {
-            let json_text = resp.text()?;
-            if let Ok(json) = json::parse(&json_text) {
-                let tags = json["graph-update"]["tags"].clone();
-                let mut tags_list = vec![];
-                for tag in tags.members() {
-                    tags_list.push(tag.clone())
-                }
-                return Ok(tags_list);
-            }
-        }
-        return Err(UrbitAPIError::FailedToFetchTags);
+            .scry_json("graph-store", "/tags")?;
+        Ok(json["graph-update"]["tags"].members().cloned().collect())
     }
 
     /// Performs a scry to get all tags
     pub fn get_tag_queries(&mut self) -> Result<Vec<JsonValue>> {
-        let resp = self
+        let json = self
             .channel
             .ship_interface
-            .scry("graph-store", "/tag-queries", "json")?;
-
-        if resp.status().as_u16() == 200 {
-            let json_text = resp.text()?;
-            if let Ok(json) = json::parse(&json_text) {
-                let tags = json["graph-update"]["tag-queries"].clone();
-                let mut tags_list = vec![];
-                for tag in tags.members() {
-                    tags_list.push(tag.clone())
-                }
-                return Ok(tags_list);
-            }
-        }
-        return Err(UrbitAPIError::FailedToFetchTags);
+            .scry_json("graph-store", "/tag-queries")?;
+        Ok(json["graph-update"]["tag-queries"]
+            .members()
+            .cloned()
+            .collect())
     }
 
     /// Acquire the time the update log of a given resource was last updated
     pub fn peek_update_log(&mut self, resource_ship: &str, resource_name: &str) -> Result<String> {
         let path = format!("/peek-update-log/{}/{}", resource_ship, resource_name);
-        let res = self
+        Ok(self
             .channel
             .ship_interface
-            .scry("graph-store", &path, "json")?;
-
-        // If successfully acquired node json
-        if res.status().as_u16() == 200 {
-            if let Ok(body) = res.text() {
-                return Ok(body);
-            }
-        }
-        // Else return error
-        Err(UrbitAPIError::FailedToGetGraph(resource_name.to_string()))
+            .scry_json("graph-store", &path)?
+            .dump())
     }
 
     /// Acquire the update log for a given resource
     pub fn get_update_log(&mut self, resource_ship: &str, resource_name: &str) -> Result<String> {
         let path = format!("/update-log/{}/{}", resource_ship, resource_name);
-        let res = self
+        Ok(self
             .channel
             .ship_interface
-            .scry("graph-store", &path, "json")?;
-
-        // If successfully acquired node json
-        if res.status().as_u16() == 200 {
-            if let Ok(body) = res.text() {
-                return Ok(body);
-            }
-        }
-        // Else return error
-        Err(UrbitAPIError::FailedToGetGraph(resource_name.to_string()))
+            .scry_json("graph-store", &path)?
+            .dump())
     }
 
     /// Acquire a subset of the update log for a given resource
@@ -615,21 +514,11 @@ impl<'a> GraphStore<'a> {
             "/update-log-subset/{}/{}/{}/{}",
             resource_ship, resource_name, end_index, start_index
         );
-        let res = self
+        Ok(self
             .channel
             .ship_interface
-            .scry("graph-store", &path, "json")?;
-
-        // If successfully acquired node json
-        if res.status().as_u16() == 200 {
-            if let Ok(body) = res.text() {
-                return Ok(body);
-            }
-        }
-        // Else return error
-        Err(UrbitAPIError::FailedToGetUpdateLog(
-            resource_name.to_string(),
-        ))
+            .scry_json("graph-store", &path)?
+            .dump())
     }
 }
 
@@ -638,6 +527,7 @@ pub fn module_to_validator_string(module: &Module) -> String {
         Module::Chat => "graph-validator-chat".to_string(),
         Module::Notebook => "graph-validator-publish".to_string(),
         Module::Collection => "graph-validator-link".to_string(),
+        Module::Post => "graph-validator-post".to_string(),
         Module::Null => "".to_string(),
     }
 }
@@ -647,6 +537,7 @@ pub fn module_to_mark(module: &Module) -> String {
         Module::Chat => "chat".to_string(),
         Module::Notebook => "publish".to_string(),
         Module::Collection => "link".to_string(),
+        Module::Post => "post".to_string(),
         Module::Null => "".to_string(),
     }
 }
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Datelike, Timelike};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 // `@ud` ~1970.1.1
@@ -11,11 +12,51 @@ pub fn unix_time_to_da(unix_time: u64) -> u128 {
     DA_UNIX_EPOCH + time_since_epoch
 }
 
+/// Convert from Urbit `@da` time to Unix time in milliseconds.
+/// Times before the Unix epoch are clamped to 0.
+pub fn da_to_unix_time(da: u128) -> u64 {
+    let time_since_epoch = da.saturating_sub(DA_UNIX_EPOCH);
+    ((time_since_epoch * 1000) / DA_SECOND) as u64
+}
+
+/// Render an Urbit `@da` time in its literal form, such as `~2021.6.3..14.05.09..8000`.
+/// Times before the Unix epoch are clamped to it.
+pub fn da_to_string(da: u128) -> String {
+    let time_since_epoch = da.saturating_sub(DA_UNIX_EPOCH);
+    let secs = (time_since_epoch / DA_SECOND).min(i64::MAX as u128) as i64;
+    let fraction = (time_since_epoch % DA_SECOND) as u64;
+    let date = match DateTime::from_timestamp(secs, 0) {
+        Some(date) => date,
+        None => return format!("~{}", da),
+    };
+
+    let mut da_string = format!("~{}.{}.{}", date.year(), date.month(), date.day());
+    if date.hour() != 0 || date.minute() != 0 || date.second() != 0 || fraction != 0 {
+        da_string += &format!(
+            "..{:02}.{:02}.{:02}",
+            date.hour(),
+            date.minute(),
+            date.second()
+        );
+    }
+    if fraction != 0 {
+        // The fraction of a second is written as 16 bit hex groups, without trailing zero groups
+        let mut groups: Vec<String> = (0..4)
+            .map(|i| format!("{:04x}", (fraction >> (48 - 16 * i)) & 0xffff))
+            .collect();
+        while groups.last().map(|g| g == "0000").unwrap_or(false) {
+            groups.pop();
+        }
+        da_string += &format!("..{}", groups.join("."));
+    }
+    da_string
+}
+
 /// Acquire the current time as u64
 pub fn get_current_time() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
-        .unwrap()
+        .unwrap_or_default()
         .as_millis() as u64
 }
 
@@ -50,3 +91,37 @@ pub fn index_dec_to_ud(index: &str) -> String {
     }
     udindex
 }
+
+/// Returns a path in the temp dir which is unique to this process and call, so that
+/// tests running in parallel (or in several `cargo test` runs) never share a file
+#[cfg(test)]
+pub(crate) fn unique_temp_path(name: &str) -> std::path::PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let count = COUNTER.fetch_add(1, Ordering::SeqCst);
+    std::env::temp_dir().join(format!(
+        "urbit-http-api-{}-{}-{}",
+        std::process::id(),
+        count,
+        name
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Verify that `@da` times are printed without the zero time and fraction parts
+    fn can_print_da() {
+        assert_eq!(da_to_string(unix_time_to_da(1622678400000)), "~2021.6.3");
+        assert_eq!(
+            da_to_string(unix_time_to_da(1622678400250)),
+            "~2021.6.3..00.00.00..4000"
+        );
+        assert_eq!(
+            da_to_string(unix_time_to_da(1622729109001)),
+            "~2021.6.3..14.05.09..0041.8937.4bc6.a7ef"
+        );
+    }
+}
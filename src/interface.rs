@@ -1,19 +1,46 @@
 use crate::channel::Channel;
 use crate::error::{Result, UrbitAPIError};
-use json::JsonValue;
-use reqwest::blocking::{Client, Response};
+use crate::retry::{RequestKind, RetryPolicy};
+use crate::scry::FromScry;
+use crate::shared_channel::SharedChannel;
+use json::{object, JsonValue};
+use reqwest::blocking::{Client, ClientBuilder, RequestBuilder, Response};
 use reqwest::header::{HeaderValue, COOKIE};
+use reqwest::{Certificate, Proxy};
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
 
-// The struct which holds the details for connecting to a given Urbit ship
+/// The session auth cookie of a `ShipInterface` along with when it expires
 #[derive(Debug, Clone)]
+struct Session {
+    cookie: HeaderValue,
+    expires: Option<SystemTime>,
+}
+
+// The struct which holds the details for connecting to a given Urbit ship
+#[derive(Clone)]
 pub struct ShipInterface {
     /// The URL of the ship given as `http://ip:port` such as
     /// `http://0.0.0.0:8080`.
     pub url: String,
-    /// The session auth string header value
-    pub session_auth: HeaderValue,
     /// The ship name (without a leading ~)
     pub ship_name: String,
+    /// The current session, which is shared between all clones of the
+    /// `ShipInterface` so that a re-login benefits all of them
+    session: Arc<RwLock<Session>>,
+    /// The `+code` of the ship, kept for re-logging in once the session expires.
+    /// `None` if the `ShipInterface` was created from a session cookie. Shared
+    /// between all clones so that a changed code benefits all of them.
+    ship_code: Arc<RwLock<Option<String>>>,
+    /// The policy used to retry requests which failed due to transient errors
+    retry_policy: RetryPolicy,
     /// The Reqwest `Client` to be reused for making requests
     req_client: Client,
 }
@@ -23,40 +50,178 @@ impl ShipInterface {
     /// `ship_url` should be `http://ip:port` of the given ship. Example:
     /// `http://0.0.0.0:8080`. `ship_code` is the code acquire from your ship
     /// by typing `+code` in dojo.
+    ///
+    /// The code is kept so that the session can be transparently renewed
+    /// if the session cookie expires (or the ship restarts).
     pub fn new(ship_url: &str, ship_code: &str) -> Result<ShipInterface> {
-        let client = Client::new();
-        let login_url = format!("{}/~/login", ship_url);
-        let resp = client
-            .post(&login_url)
-            .body("password=".to_string() + &ship_code)
-            .send()?;
+        ShipInterface::builder(ship_url).login(ship_code)
+    }
 
-        // Check for status code
-        if resp.status().as_u16() != 204 {
-            return Err(UrbitAPIError::FailedToLogin);
-        }
+    /// Creates a new `ShipInterface` from an existing `urbauth-~ship` session cookie
+    /// rather than the `+code`, verifying that the ship accepts the cookie.
+    /// `cookie` may either be the `cookie` or the `set-cookie` header value.
+    ///
+    /// As the code is not known, the session can not be renewed once it expires.
+    pub fn from_cookie(ship_url: &str, cookie: &str) -> Result<ShipInterface> {
+        ShipInterface::builder(ship_url).login_with_cookie(cookie)
+    }
 
-        // Acquire the session auth header value
-        let session_auth = resp
-            .headers()
-            .get("set-cookie")
-            .ok_or(UrbitAPIError::FailedToLogin)?;
+    /// Create a `ShipInterfaceBuilder` for configuring the HTTP client (timeouts, proxies,
+    /// certificates, etc.) and `RetryPolicy` before connecting to the ship at `ship_url`.
+    pub fn builder(ship_url: &str) -> ShipInterfaceBuilder {
+        ShipInterfaceBuilder {
+            url: ship_url.to_string(),
+            client: None,
+            client_builder: Client::builder(),
+            retry_policy: RetryPolicy::default(),
+        }
+    }
 
-        // Convert sessions auth to a string
-        let auth_string = session_auth
+    /// Saves the current session (the session cookie and its expiry) as json to the
+    /// file at `path`, so that it can later be restored via `load_session` without the `+code`.
+    /// The file grants access to the ship, so it should be kept private.
+    pub fn save_session(&self, path: &str) -> Result<()> {
+        let session = self.session();
+        let cookie = session
+            .cookie
             .to_str()
-            .map_err(|_| UrbitAPIError::FailedToLogin)?;
+            .map_err(|_| UrbitAPIError::FailedToSaveSession(path.to_string()))?;
+        let expires = session
+            .expires
+            .and_then(|e| e.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|e| e.as_secs());
+        let json = object! {
+            "url": self.url.clone(),
+            "ship": self.ship_name.clone(),
+            "cookie": cookie,
+            "expires": expires,
+        };
 
-        // Trim the auth string to acquire the ship name
-        let end_pos = auth_string.find('=').ok_or(UrbitAPIError::FailedToLogin)?;
-        let ship_name = &auth_string[9..end_pos];
+        write_private_file(path, &json.pretty(2))
+            .map_err(|e| UrbitAPIError::FailedToSaveSession(format!("{}: {}", path, e)))
+    }
 
-        Ok(ShipInterface {
-            url: ship_url.to_string(),
-            session_auth: session_auth.clone(),
-            ship_name: ship_name.to_string(),
-            req_client: client,
-        })
+    /// Restores a session which was saved via `save_session`, verifying that it has not
+    /// expired and that the ship still accepts it.
+    pub fn load_session(path: &str) -> Result<ShipInterface> {
+        if !Path::new(path).exists() {
+            return Err(UrbitAPIError::FailedToLoadSession(path.to_string()));
+        }
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| UrbitAPIError::FailedToLoadSession(format!("{}: {}", path, e)))?;
+        let json = json::parse(&contents)
+            .map_err(|e| UrbitAPIError::FailedToLoadSession(format!("{}: {}", path, e)))?;
+
+        let url = json["url"]
+            .as_str()
+            .ok_or_else(|| UrbitAPIError::FailedToLoadSession(path.to_string()))?;
+        let cookie = json["cookie"]
+            .as_str()
+            .ok_or_else(|| UrbitAPIError::FailedToLoadSession(path.to_string()))?;
+        let expires = json["expires"]
+            .as_u64()
+            .map(|e| SystemTime::UNIX_EPOCH + Duration::from_secs(e));
+        if let Some(expires) = expires {
+            if expires <= SystemTime::now() {
+                return Err(UrbitAPIError::InvalidSession(format!(
+                    "session saved at {} has expired",
+                    path
+                )));
+            }
+        }
+
+        let ship_interface = ShipInterface::from_cookie(url, cookie)?;
+        if let Ok(mut session) = ship_interface.session.write() {
+            session.expires = expires;
+        }
+        Ok(ship_interface)
+    }
+
+    /// Set the `RetryPolicy` used for scries, spider threads and channel requests
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> ShipInterface {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Returns the `RetryPolicy` used for scries, spider threads and channel requests
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    /// Returns the current session auth header value.
+    ///
+    /// Since 0.8.0 this replaces the former public `session_auth` field, as the session
+    /// may now be renewed by logging in again, and a copied field would go stale.
+    pub fn session_auth(&self) -> HeaderValue {
+        self.session().cookie
+    }
+
+    /// Returns when the current session expires, if known
+    pub fn session_expiry(&self) -> Option<SystemTime> {
+        self.session().expires
+    }
+
+    /// Logs into the ship again using the stored code, replacing the session
+    /// auth of this `ShipInterface` and all of its clones. Fails if the
+    /// `ShipInterface` was created from a session cookie (and thus has no code).
+    pub fn relogin(&self) -> Result<()> {
+        let ship_code = self.ship_code().ok_or(UrbitAPIError::FailedToLogin)?;
+        let session_auth = login(&self.req_client, &self.url, &ship_code)?;
+        match self.session.write() {
+            Ok(mut current) => *current = Session::new(session_auth),
+            Err(poisoned) => *poisoned.into_inner() = Session::new(session_auth),
+        }
+        Ok(())
+    }
+
+    /// Replaces the `+code` used to log in again once the session expires, for this
+    /// `ShipInterface` and all of its clones. Needed after the code of the ship has been
+    /// changed (ex. via `Hood::reset_code`), while `None` disables logging in again.
+    pub fn set_ship_code(&self, ship_code: Option<&str>) {
+        let ship_code = ship_code.map(|c| c.to_string());
+        match self.ship_code.write() {
+            Ok(mut current) => *current = ship_code,
+            Err(poisoned) => *poisoned.into_inner() = ship_code,
+        }
+    }
+
+    /// Internal method which returns a copy of the stored `+code`, if any
+    fn ship_code(&self) -> Option<String> {
+        match self.ship_code.read() {
+            Ok(ship_code) => ship_code.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// The HTTP client which is used for all requests to the ship
+    pub(crate) fn client(&self) -> &Client {
+        &self.req_client
+    }
+
+    /// Internal method which returns a copy of the current session
+    fn session(&self) -> Session {
+        match self.session.read() {
+            Ok(session) => session.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Logs out of the ship, invalidating the current session cookie.
+    /// Note that any further request made via this `ShipInterface` logs in again.
+    pub fn logout(&self) -> Result<()> {
+        let logout_url = format!("{}/~/logout", self.url);
+        let resp = self
+            .req_client
+            .post(&logout_url)
+            .header(COOKIE, self.session_auth())
+            .send()?;
+
+        // Eyre redirects to the login page after logging out
+        if resp.status().is_success() || resp.status().is_redirection() {
+            Ok(())
+        } else {
+            Err(UrbitAPIError::from_response(resp))
+        }
     }
 
     /// Returns the ship name with a leading `~` (By default ship_name does not have one)
@@ -69,29 +234,65 @@ impl ShipInterface {
         Channel::new(self.clone())
     }
 
+    /// Create a `SharedChannel` using this `ShipInterface`
+    pub fn create_shared_channel(&self) -> Result<SharedChannel> {
+        SharedChannel::new(self.clone())
+    }
+
     // Send a put request using the `ShipInterface`
     pub fn send_put_request(&self, url: &str, body: &JsonValue) -> Result<Response> {
         let json = body.dump();
-        let resp = self
-            .req_client
-            .put(url)
-            .header(COOKIE, self.session_auth.clone())
-            .header("Content-Type", "application/json")
-            .body(json);
-
-        Ok(resp.send()?)
+        // Retrying pokes could make them take effect twice
+        let idempotent = !body.members().any(|action| action["action"] == "poke");
+        let kind = RequestKind::ChannelRequest { idempotent };
+        self.send_request(kind, |client, session_auth| {
+            client
+                .put(url)
+                .header(COOKIE, session_auth)
+                .header("Content-Type", "application/json")
+                .body(json.clone())
+        })
     }
 
     /// Sends a scry to the ship
     pub fn scry(&self, app: &str, path: &str, mark: &str) -> Result<Response> {
         let scry_url = format!("{}/~/scry/{}{}.{}", self.url, app, path, mark);
-        let resp = self
-            .req_client
-            .get(&scry_url)
-            .header(COOKIE, self.session_auth.clone())
-            .header("Content-Type", "application/json");
+        self.send_request(RequestKind::Scry, |client, session_auth| {
+            client
+                .get(&scry_url)
+                .header(COOKIE, session_auth)
+                .header("Content-Type", "application/json")
+        })
+    }
+
+    /// Sends a scry with the `json` mark to the ship and parses the result
+    pub fn scry_json(&self, app: &str, path: &str) -> Result<JsonValue> {
+        let resp = self.scry(app, path, "json")?;
+        // Return the error from the ship if the scry failed
+        if resp.status().as_u16() != 200 {
+            return Err(UrbitAPIError::from_response(resp));
+        }
+        let body = resp.text()?;
+        json::parse(&body)
+            .map_err(|_| UrbitAPIError::FailedToDecodeScry(format!("/{}{}", app, path)))
+    }
 
-        Ok(resp.send()?)
+    /// Sends a scry with the `json` mark to the ship and decodes the result as `T`.
+    /// The path can be built via `ScryPath`.
+    pub fn scry_typed<T: FromScry>(&self, app: &str, path: &str) -> Result<T> {
+        let json = self.scry_json(app, path)?;
+        T::from_scry(&json)
+    }
+
+    /// Sends a scry with a mark which is not json (ex. `noun`, `txt` or `mime`)
+    /// and returns the raw bytes of the result. A `noun` is returned jammed.
+    pub fn scry_bytes(&self, app: &str, path: &str, mark: &str) -> Result<Vec<u8>> {
+        let resp = self.scry(app, path, mark)?;
+        // Return the error from the ship if the scry failed
+        if resp.status().as_u16() != 200 {
+            return Err(UrbitAPIError::from_response(resp));
+        }
+        Ok(resp.bytes()?.to_vec())
     }
 
     /// Run a thread via spider
@@ -108,14 +309,445 @@ impl ShipInterface {
             self.url, input_mark, thread_name, output_mark
         );
 
-        let resp = self
+        self.send_request(RequestKind::Spider, |client, session_auth| {
+            client
+                .post(&spider_url)
+                .header(COOKIE, session_auth)
+                .header("Content-Type", "application/json")
+                .body(json.clone())
+        })
+    }
+
+    /// Runs a thread on the ship and decodes its output. The thread is looked up in the
+    /// `/ted` folder of the given `desk` (ex. `base`); an empty `desk` uses the legacy
+    /// spider endpoint which only runs threads from the `base` desk.
+    ///
+    /// If the thread fails, the returned `ThreadFailed` error contains its tang.
+    pub fn run_thread(
+        &self,
+        desk: &str,
+        thread_name: &str,
+        input_mark: &str,
+        output_mark: &str,
+        body: &JsonValue,
+    ) -> Result<JsonValue> {
+        self.run_thread_inner(desk, thread_name, input_mark, output_mark, body, None)
+    }
+
+    /// Runs a thread on the ship like `run_thread`, waiting up to `timeout` for it to
+    /// finish rather than the timeout of the HTTP client. Useful for long-running threads.
+    pub fn run_thread_with_timeout(
+        &self,
+        desk: &str,
+        thread_name: &str,
+        input_mark: &str,
+        output_mark: &str,
+        body: &JsonValue,
+        timeout: Duration,
+    ) -> Result<JsonValue> {
+        self.run_thread_inner(
+            desk,
+            thread_name,
+            input_mark,
+            output_mark,
+            body,
+            Some(timeout),
+        )
+    }
+
+    /// Internal method which runs a thread with an optional request timeout
+    fn run_thread_inner(
+        &self,
+        desk: &str,
+        thread_name: &str,
+        input_mark: &str,
+        output_mark: &str,
+        body: &JsonValue,
+        timeout: Option<Duration>,
+    ) -> Result<JsonValue> {
+        let json = body.dump();
+        let spider_url = if desk.is_empty() {
+            format!(
+                "{}/spider/{}/{}/{}.json",
+                self.url, input_mark, thread_name, output_mark
+            )
+        } else {
+            format!(
+                "{}/spider/{}/{}/{}/{}",
+                self.url, desk, input_mark, thread_name, output_mark
+            )
+        };
+
+        let resp = self.send_request(RequestKind::Spider, |client, session_auth| {
+            let request = client
+                .post(&spider_url)
+                .header(COOKIE, session_auth)
+                .header("Content-Type", "application/json")
+                .body(json.clone());
+            match timeout {
+                Some(timeout) => request.timeout(timeout),
+                None => request,
+            }
+        })?;
+
+        let status = resp.status().as_u16();
+        if status == 200 {
+            let output = resp.text()?;
+            // Outputs with marks other than json may not be json
+            return Ok(json::parse(&output).unwrap_or(JsonValue::String(output)));
+        }
+        // A failed thread responds with a 500 and its tang
+        if status == 500 {
+            let body = resp.text().unwrap_or_default();
+            return Err(UrbitAPIError::ThreadFailed {
+                thread: thread_name.to_string(),
+                tang: tang_from_body(&body),
+            });
+        }
+        Err(UrbitAPIError::from_response(resp))
+    }
+
+    /// Internal method which sends the request built by `build_request`, retrying
+    /// it as per the `RetryPolicy` if it fails due to a transient error.
+    fn send_request<F>(&self, kind: RequestKind, build_request: F) -> Result<Response>
+    where
+        F: Fn(&Client, HeaderValue) -> RequestBuilder,
+    {
+        let mut attempt = 1;
+        loop {
+            let result = self.send_with_reauth(&build_request);
+            if !self.retry_policy.should_retry(kind, attempt, &result) {
+                return result;
+            }
+            thread::sleep(self.retry_policy.backoff_for(attempt));
+            attempt += 1;
+        }
+    }
+
+    /// Internal method which sends the request built by `build_request` with the
+    /// current session auth. If the ship rejects the session, logs in again and
+    /// retries the request once.
+    fn send_with_reauth<F>(&self, build_request: &F) -> Result<Response>
+    where
+        F: Fn(&Client, HeaderValue) -> RequestBuilder,
+    {
+        let resp = build_request(&self.req_client, self.session_auth()).send()?;
+        // Without the code the session can not be renewed
+        if !session_rejected(&resp) || self.ship_code().is_none() {
+            return Ok(resp);
+        }
+
+        self.relogin()?;
+        Ok(build_request(&self.req_client, self.session_auth()).send()?)
+    }
+}
+
+/// A builder for a `ShipInterface` which allows configuring the HTTP client used for
+/// all requests to the ship (including channel event streams) and the `RetryPolicy`.
+///
+/// ```ignore
+/// let ship_interface = ShipInterface::builder("https://sampel-palnet.arvo.network")
+///     .timeout(Duration::from_secs(10))
+///     .user_agent("my-urbit-tool")
+///     .login("lidlut-tabwed-pillex-ridrup")?;
+/// ```
+#[derive(Debug)]
+pub struct ShipInterfaceBuilder {
+    url: String,
+    /// A prebuilt client, which takes precedence over `client_builder`
+    client: Option<Client>,
+    client_builder: ClientBuilder,
+    retry_policy: RetryPolicy,
+}
+
+impl ShipInterfaceBuilder {
+    /// Set the timeout of requests, from connecting until the response body has been read.
+    /// Channel event streams are not affected as they are expected to stay open.
+    pub fn timeout(mut self, timeout: Duration) -> ShipInterfaceBuilder {
+        self.client_builder = self.client_builder.timeout(timeout);
+        self
+    }
+
+    /// Set the timeout for connecting to the ship
+    pub fn connect_timeout(mut self, timeout: Duration) -> ShipInterfaceBuilder {
+        self.client_builder = self.client_builder.connect_timeout(timeout);
+        self
+    }
+
+    /// Send requests via the given proxy
+    pub fn proxy(mut self, proxy: Proxy) -> ShipInterfaceBuilder {
+        self.client_builder = self.client_builder.proxy(proxy);
+        self
+    }
+
+    /// Trust an additional root certificate, such as that of a self-signed ship
+    pub fn add_root_certificate(mut self, certificate: Certificate) -> ShipInterfaceBuilder {
+        self.client_builder = self.client_builder.add_root_certificate(certificate);
+        self
+    }
+
+    /// Set the `User-Agent` header sent with every request
+    pub fn user_agent(mut self, user_agent: &str) -> ShipInterfaceBuilder {
+        self.client_builder = self.client_builder.user_agent(user_agent.to_string());
+        self
+    }
+
+    /// Accept invalid (ex. self-signed or expired) TLS certificates.
+    /// This is dangerous, as it allows anyone to impersonate the ship.
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> ShipInterfaceBuilder {
+        self.client_builder = self.client_builder.danger_accept_invalid_certs(accept);
+        self
+    }
+
+    /// Use a prebuilt client, ignoring all other client settings of the builder
+    pub fn client(mut self, client: Client) -> ShipInterfaceBuilder {
+        self.client = Some(client);
+        self
+    }
+
+    /// Set the `RetryPolicy` used for scries, spider threads and channel requests
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> ShipInterfaceBuilder {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Logs into the ship with the `+code` and creates the `ShipInterface`
+    pub fn login(self, ship_code: &str) -> Result<ShipInterface> {
+        let retry_policy = self.retry_policy.clone();
+        let url = self.url.clone();
+        let client = self.build_client()?;
+        let session_auth = login(&client, &url, ship_code)?;
+        let ship_name = discover_ship_name(&client, &url, &session_auth)?;
+
+        Ok(ShipInterface {
+            url,
+            ship_name,
+            session: Arc::new(RwLock::new(Session::new(session_auth))),
+            ship_code: Arc::new(RwLock::new(Some(ship_code.to_string()))),
+            retry_policy,
+            req_client: client,
+        })
+    }
+
+    /// Creates the `ShipInterface` from an existing `urbauth-~ship` session cookie,
+    /// verifying that the ship accepts the cookie (see `ShipInterface::from_cookie`).
+    pub fn login_with_cookie(self, cookie: &str) -> Result<ShipInterface> {
+        let session_auth = HeaderValue::from_str(cookie)
+            .map_err(|_| UrbitAPIError::InvalidSession(cookie.to_string()))?;
+
+        let retry_policy = self.retry_policy.clone();
+        let url = self.url.clone();
+        let client = self.build_client()?;
+        let ship_name = discover_ship_name(&client, &url, &session_auth)?;
+        let ship_interface = ShipInterface {
+            ship_name,
+            session: Arc::new(RwLock::new(Session::new(session_auth))),
+            ship_code: Arc::new(RwLock::new(None)),
+            retry_policy,
+            req_client: client,
+            url,
+        };
+
+        // Verify the session with a cheap request, which is rejected if not authenticated
+        let resp = ship_interface
             .req_client
-            .post(&spider_url)
-            .header(COOKIE, self.session_auth.clone())
-            .header("Content-Type", "application/json")
-            .body(json);
+            .get(format!("{}/~/name", ship_interface.url))
+            .header(COOKIE, ship_interface.session_auth())
+            .send()?;
+        if resp.status().as_u16() != 200 || session_rejected(&resp) {
+            return Err(UrbitAPIError::InvalidSession(ship_interface.url));
+        }
+        Ok(ship_interface)
+    }
+
+    /// Internal method which returns the prebuilt client, or builds one from the settings
+    fn build_client(self) -> Result<Client> {
+        match self.client {
+            Some(client) => Ok(client),
+            None => Ok(self.client_builder.build()?),
+        }
+    }
+}
 
-        Ok(resp.send()?)
+impl fmt::Debug for ShipInterface {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The session auth and code are left out to avoid leaking them into logs
+        f.debug_struct("ShipInterface")
+            .field("url", &self.url)
+            .field("ship_name", &self.ship_name)
+            .finish()
+    }
+}
+
+impl Session {
+    /// Create a new `Session` from the `set-cookie` header value, reading the expiry
+    /// from its `Max-Age` (or `max-age`) attribute
+    fn new(cookie: HeaderValue) -> Session {
+        let expires = cookie.to_str().ok().and_then(|c| {
+            c.split(';')
+                .filter_map(|attribute| attribute.trim().split_once('='))
+                .find(|(key, _)| key.eq_ignore_ascii_case("max-age"))
+                .and_then(|(_, value)| value.trim().parse::<u64>().ok())
+                .map(|max_age| SystemTime::now() + Duration::from_secs(max_age))
+        });
+        Session { cookie, expires }
+    }
+}
+
+/// Acquires the ship name (without a leading ~) from an `urbauth-~ship=...` cookie.
+/// The cookie may be preceded or followed by other cookies and attributes.
+fn ship_name_from_cookie(cookie: &HeaderValue) -> Option<String> {
+    // Convert sessions auth to a string
+    let auth_string = cookie.to_str().ok()?;
+    // Find the session cookie among the `name=value` pairs
+    auth_string
+        .split([';', ','])
+        .filter_map(|pair| pair.trim().split_once('='))
+        .filter_map(|(name, _)| name.strip_prefix("urbauth-"))
+        .map(|name| name.trim_start_matches('~').to_string())
+        .find(|name| is_valid_patp(name))
+}
+
+/// Checks whether the string is a valid @p (with or without a leading ~), such as
+/// `zod`, `sampel-palnet`, a moon `doznec-sampel-palnet` or a comet (with `--`).
+fn is_valid_patp(name: &str) -> bool {
+    let name = name.strip_prefix('~').unwrap_or(name);
+    if name.len() == 3 {
+        return name.chars().all(|c| c.is_ascii_lowercase());
+    }
+    let mut words = name.split('-').peekable();
+    let mut count = 0;
+    while let Some(word) = words.next() {
+        // Comets separate the two halves of their name with `--`
+        if word.is_empty() && count % 4 == 0 && count > 0 && words.peek().is_some() {
+            continue;
+        }
+        if word.len() != 6 || !word.chars().all(|c| c.is_ascii_lowercase()) {
+            return false;
+        }
+        count += 1;
+    }
+    count > 0 && count <= 8
+}
+
+/// Asks Eyre for the name of the ship which the session belongs to, used when the
+/// name can not be read from the session cookie
+fn ship_name_from_eyre(client: &Client, ship_url: &str, cookie: &HeaderValue) -> Option<String> {
+    ["/~/name", "/~/host"].iter().find_map(|endpoint| {
+        let resp = client
+            .get(format!("{}{}", ship_url, endpoint))
+            .header(COOKIE, cookie.clone())
+            .send()
+            .ok()?;
+        if resp.status().as_u16() != 200 {
+            return None;
+        }
+        let name = resp.text().ok()?;
+        let name = name.trim().trim_matches('"').trim_start_matches('~');
+        if is_valid_patp(name) {
+            Some(name.to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Discovers the name of the ship from the session cookie, falling back to asking Eyre
+fn discover_ship_name(client: &Client, ship_url: &str, cookie: &HeaderValue) -> Result<String> {
+    ship_name_from_cookie(cookie)
+        .or_else(|| ship_name_from_eyre(client, ship_url, cookie))
+        .ok_or_else(|| UrbitAPIError::FailedToDiscoverShipName(ship_url.to_string()))
+}
+
+/// Logs into the ship at the url with the code, returning the session auth header value
+fn login(client: &Client, ship_url: &str, ship_code: &str) -> Result<HeaderValue> {
+    let login_url = format!("{}/~/login", ship_url);
+    let resp = client
+        .post(&login_url)
+        .body("password=".to_string() + ship_code)
+        .send()?;
+
+    // Check for status code
+    if resp.status().as_u16() != 204 {
+        return Err(UrbitAPIError::FailedToLogin);
+    }
+
+    // Acquire the session auth header value, preferring the `urbauth-` cookie
+    // if the ship sets multiple cookies
+    let cookies: Vec<&HeaderValue> = resp.headers().get_all("set-cookie").iter().collect();
+    cookies
+        .iter()
+        .find(|cookie| {
+            cookie
+                .to_str()
+                .map(|c| c.trim_start().starts_with("urbauth-"))
+                .unwrap_or(false)
+        })
+        .or_else(|| cookies.first())
+        .map(|cookie| (*cookie).clone())
+        .ok_or(UrbitAPIError::FailedToLogin)
+}
+
+/// Renders the tang which spider responds with when a thread fails as text. The tang
+/// is either a json array of lines (possibly nested) or plain text.
+pub(crate) fn tang_from_body(body: &str) -> String {
+    fn collect_lines(json: &JsonValue, lines: &mut Vec<String>) {
+        if json.is_array() {
+            json.members()
+                .for_each(|member| collect_lines(member, lines));
+        } else if let Some(line) = json.as_str() {
+            lines.push(line.to_string());
+        } else if !json.is_null() {
+            lines.push(json.dump());
+        }
+    }
+
+    match json::parse(body) {
+        Ok(json) if json.is_array() => {
+            let mut lines = vec![];
+            collect_lines(&json, &mut lines);
+            lines.join("\n")
+        }
+        _ => body.trim().to_string(),
+    }
+}
+
+/// Writes the contents to the file at `path`, which is only readable and writable
+/// by the current user on unix
+fn write_private_file(path: &str, contents: &str) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path)?;
+    // The mode only applies to new files, so restrict existing ones as well
+    #[cfg(unix)]
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    file.write_all(contents.as_bytes())
+}
+
+/// Checks whether the ship rejected the session of the request, either via a
+/// 401/403 status or by redirecting to the login page
+fn session_rejected(resp: &Response) -> bool {
+    let status = resp.status().as_u16();
+    status == 401 || status == 403 || resp.url().path().starts_with("/~/login")
+}
+
+#[cfg(test)]
+impl ShipInterface {
+    /// Create a `ShipInterface` for `~zod` without logging in, used to test code which
+    /// does not need a running ship. Requests fail as nothing is listening at the url.
+    pub(crate) fn offline() -> ShipInterface {
+        ShipInterface {
+            url: "http://127.0.0.1:9".to_string(),
+            ship_name: "zod".to_string(),
+            session: Arc::new(RwLock::new(Session::new(HeaderValue::from_static(
+                "urbauth-~zod=0v0",
+            )))),
+            ship_code: Arc::new(RwLock::new(None)),
+            retry_policy: RetryPolicy::never(),
+            req_client: Client::new(),
+        }
     }
 }
 
@@ -130,6 +762,46 @@ mod tests {
     use super::*;
     use crate::subscription::Subscription;
     use json::object;
+    #[test]
+    #[cfg(unix)]
+    // Verify that saved sessions are only readable by the current user
+    fn session_files_are_private() {
+        let path = crate::helper::unique_temp_path("session.json");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "").unwrap();
+        write_private_file(path, "{}").unwrap();
+        let mode = std::fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "{}");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    // Verify that a changed `+code` is seen by every clone of a `ShipInterface`
+    fn ship_code_is_shared_between_clones() {
+        let ship_interface = ShipInterface::offline();
+        let clone = ship_interface.clone();
+        assert_eq!(clone.ship_code(), None);
+        ship_interface.set_ship_code(Some("lidlut-tabwed-pillex-ridrup"));
+        assert_eq!(
+            clone.ship_code().as_deref(),
+            Some("lidlut-tabwed-pillex-ridrup")
+        );
+        clone.set_ship_code(None);
+        assert_eq!(ship_interface.ship_code(), None);
+    }
+
+    #[test]
+    // Verify that the tang of a failed thread is rendered line by line
+    fn can_parse_thread_tang() {
+        let body = r#"["thread failed", ["/lib/strandio/hoon::[1 2].[3 4]", "bail"]]"#;
+        assert_eq!(
+            tang_from_body(body),
+            "thread failed\n/lib/strandio/hoon::[1 2].[3 4]\nbail"
+        );
+        assert_eq!(tang_from_body("nest-fail\n"), "nest-fail");
+    }
+
     #[test]
     // Verify that we can login to a local `~zod` dev ship.
     fn can_login() {
@@ -137,6 +809,30 @@ mod tests {
             ShipInterface::new("http://0.0.0.0:8080", "lidlut-tabwed-pillex-ridrup").unwrap();
     }
 
+    #[test]
+    // Verify that the ship name and expiry are read from a session cookie
+    fn can_parse_session_cookie() {
+        let cookie = HeaderValue::from_static(
+            "urbauth-~zod=0v5.fvqk1.r2fjl.e8ijb.i4fgq.qhnra; Path=/; Max-Age=604800",
+        );
+        assert_eq!(ship_name_from_cookie(&cookie).unwrap(), "zod");
+        assert!(Session::new(cookie).expires.unwrap() > SystemTime::now());
+
+        let cookie = HeaderValue::from_static(
+            "theme=dark; urbauth-~doznec-sampel-palnet=0v5.fvqk1.r2fjl; Path=/; Max-Age=604800",
+        );
+        assert_eq!(
+            ship_name_from_cookie(&cookie).unwrap(),
+            "doznec-sampel-palnet"
+        );
+        assert!(is_valid_patp("~sampel-palnet"));
+        assert!(is_valid_patp(
+            "dasres-ragnep-lislyt-ritpur--fadtul-ragnep-lislyt-ritpur"
+        ));
+        assert!(!is_valid_patp("Sampel-palnet"));
+        assert!(!is_valid_patp("sampel-"));
+    }
+
     #[test]
     // Verify that we can create a channel
     fn can_create_channel() {
@@ -161,6 +857,18 @@ mod tests {
         channel.delete_channel();
     }
 
+    #[test]
+    // Verify that we can subscribe via a shared channel
+    fn can_subscribe_shared() {
+        let ship_interface =
+            ShipInterface::new("http://0.0.0.0:8080", "lidlut-tabwed-pillex-ridrup").unwrap();
+        let channel = ship_interface.create_shared_channel().unwrap();
+        let subscription = channel.subscribe("graph-store", "/updates").unwrap();
+
+        channel.unsubscribe(subscription.creation_id).unwrap();
+        channel.delete_channel().unwrap();
+    }
+
     #[test]
     // Verify that we can make a poke
     fn can_poke() {
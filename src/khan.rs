@@ -0,0 +1,235 @@
+use crate::error::{Result, UrbitAPIError};
+use crate::interface::tang_from_body;
+use crate::shared_channel::{SharedChannel, SharedSubscription};
+use crossbeam::channel::{RecvTimeoutError, TryRecvError};
+use json::{object, JsonValue};
+use rand::Rng;
+use reqwest::blocking::Response;
+use std::time::Duration;
+
+/// A handle to a thread which was started over a channel by poking `spider`
+/// with `spider-start`. The result of the thread arrives asynchronously on its
+/// `/thread-result/<tid>` subscription, and can either be polled via `try_result`
+/// or waited for via `wait`/`wait_timeout`.
+///
+/// ```ignore
+/// let channel = ship_interface.create_shared_channel()?;
+/// let mut thread = channel.start_thread("base", "my-thread", "noun", &json)?;
+/// match thread.wait_timeout(Duration::from_secs(30)) {
+///     Some(Ok(output)) => println!("{}", output),
+///     Some(Err(e)) => println!("{}", e),
+///     None => {
+///         thread.cancel()?;
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ThreadHandle {
+    /// The id of the running thread
+    pub tid: String,
+    /// The name of the thread
+    pub thread_name: String,
+    channel: SharedChannel,
+    subscription: SharedSubscription,
+    /// The result of the thread, once it has been received
+    result: Option<Result<JsonValue>>,
+    /// Whether the result has been received
+    finished: bool,
+}
+
+impl SharedChannel {
+    /// Starts the thread `thread_name` from the `/ted` folder of the `desk` by poking
+    /// `spider`, passing it `body` with the `input_mark`. Returns a `ThreadHandle` which
+    /// receives the result of the thread.
+    pub fn start_thread(
+        &self,
+        desk: &str,
+        thread_name: &str,
+        input_mark: &str,
+        body: &JsonValue,
+    ) -> Result<ThreadHandle> {
+        let tid = new_tid(thread_name);
+        // Watch for the result before starting the thread so that it is not missed
+        let subscription = self.subscribe_raw("spider", &format!("/thread-result/{}", tid))?;
+
+        let start_args = object! {
+            "parent": JsonValue::Null,
+            "use": tid.clone(),
+            "desk": desk,
+            "thread": thread_name,
+            "input": object! {
+                "mark": input_mark,
+                "json": body.clone(),
+            },
+        };
+        let resp = self.poke("spider", "spider-start", &start_args)?;
+        if resp.status().as_u16() != 204 {
+            let _res = self.unsubscribe(subscription.creation_id);
+            return Err(UrbitAPIError::from_response(resp));
+        }
+
+        Ok(ThreadHandle {
+            tid,
+            thread_name: thread_name.to_string(),
+            channel: self.clone(),
+            subscription,
+            result: None,
+            finished: false,
+        })
+    }
+}
+
+impl ThreadHandle {
+    /// Checks whether the thread has finished without blocking, returning its
+    /// result if so. A failed thread returns a `ThreadFailed` error with its tang.
+    pub fn try_result(&mut self) -> Option<&Result<JsonValue>> {
+        if !self.finished {
+            match self.subscription.receiver.try_recv() {
+                Ok(event) => self.set_result(self.parse_result(&event)),
+                Err(TryRecvError::Disconnected) => self.set_result(self.ended_early()),
+                Err(TryRecvError::Empty) => (),
+            }
+        }
+        self.result.as_ref()
+    }
+
+    /// Blocks until the thread has finished and returns its result
+    pub fn wait(mut self) -> Result<JsonValue> {
+        if let Some(result) = self.result.take() {
+            return result;
+        }
+        self.finished = true;
+        match self.subscription.receiver.recv() {
+            Ok(event) => self.parse_result(&event),
+            Err(_) => self.ended_early(),
+        }
+    }
+
+    /// Blocks until the thread has finished or the timeout has passed, returning
+    /// the result if the thread finished in time.
+    pub fn wait_timeout(&mut self, timeout: Duration) -> Option<&Result<JsonValue>> {
+        if !self.finished {
+            match self.subscription.receiver.recv_timeout(timeout) {
+                Ok(event) => self.set_result(self.parse_result(&event)),
+                Err(RecvTimeoutError::Disconnected) => self.set_result(self.ended_early()),
+                Err(RecvTimeoutError::Timeout) => (),
+            }
+        }
+        self.result.as_ref()
+    }
+
+    /// Stops the thread by poking `spider` with `spider-stop`
+    pub fn cancel(&self) -> Result<Response> {
+        self.channel.poke(
+            "spider",
+            "spider-stop",
+            &object! {
+                "tid": self.tid.clone(),
+                "nice": false,
+            },
+        )
+    }
+
+    /// Internal method which stores the result of the finished thread
+    fn set_result(&mut self, result: Result<JsonValue>) {
+        self.result = Some(result);
+        self.finished = true;
+    }
+
+    /// Internal method which decodes a `thread-done` or `thread-fail` fact
+    fn parse_result(&self, event: &JsonValue) -> Result<JsonValue> {
+        parse_thread_result(&self.thread_name, event)
+    }
+
+    /// Internal method for the error when the subscription ended without a result
+    fn ended_early(&self) -> Result<JsonValue> {
+        Err(UrbitAPIError::ThreadFailed {
+            thread: self.thread_name.clone(),
+            tang: "the thread ended without a result".to_string(),
+        })
+    }
+}
+
+impl Drop for ThreadHandle {
+    fn drop(&mut self) {
+        // Stop watching for the result, as spider only ends the subscription once it arrives
+        if !self.finished {
+            let _res = self.channel.unsubscribe(self.subscription.creation_id);
+        }
+    }
+}
+
+/// Decodes the result of a thread from a `thread-done` or `thread-fail` fact, where
+/// the json of a `thread-fail` is the error `[term tang]`
+fn parse_thread_result(thread_name: &str, event: &JsonValue) -> Result<JsonValue> {
+    match event["mark"].as_str() {
+        Some("thread-done") => Ok(event["json"].clone()),
+        Some("thread-fail") => Err(UrbitAPIError::ThreadFailed {
+            thread: thread_name.to_string(),
+            tang: tang_from_body(&event["json"].dump()),
+        }),
+        _ => Err(UrbitAPIError::ThreadFailed {
+            thread: thread_name.to_string(),
+            tang: format!("unexpected thread result {}", event.dump()),
+        }),
+    }
+}
+
+/// Creates a unique thread id, which must be a valid `@ta`
+fn new_tid(thread_name: &str) -> String {
+    let name: String = thread_name
+        .chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            c @ ('a'..='z' | '0'..='9' | '-') => c,
+            _ => '-',
+        })
+        .collect();
+    format!("{}--{:x}", name, rand::thread_rng().gen::<u64>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Verify that thread results are decoded from their facts
+    fn can_parse_thread_result() {
+        let done = object! {"response": "diff", "mark": "thread-done", "json": {"a": 1}};
+        assert_eq!(
+            parse_thread_result("test", &done).unwrap(),
+            object! {"a": 1}
+        );
+
+        let fail = object! {
+            "response": "diff",
+            "mark": "thread-fail",
+            "json": ["nest-fail", ["/ted/test/hoon::[1 2].[3 4]", "need: @"]],
+        };
+        match parse_thread_result("test", &fail) {
+            Err(UrbitAPIError::ThreadFailed { thread, tang }) => {
+                assert_eq!(thread, "test");
+                assert_eq!(tang, "nest-fail\n/ted/test/hoon::[1 2].[3 4]\nneed: @");
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        let unexpected = object! {"response": "diff", "mark": "noun", "json": null};
+        assert!(matches!(
+            parse_thread_result("test", &unexpected),
+            Err(UrbitAPIError::ThreadFailed { .. })
+        ));
+    }
+
+    #[test]
+    // Verify that thread ids are valid `@ta`s
+    fn tids_are_valid_knots() {
+        for name in &["graph-create", "My_Thread", "a.b c~"] {
+            let tid = new_tid(name);
+            assert!(tid
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-'));
+        }
+        assert!(new_tid("My_Thread").starts_with("my-thread--"));
+        assert_ne!(new_tid("test"), new_tid("test"));
+    }
+}
@@ -1,27 +1,48 @@
 pub mod apps;
+pub mod bot;
 pub mod channel;
 pub mod error;
+mod event_stream;
 pub mod graph;
 pub mod graphstore;
 pub mod helper;
 pub mod interface;
+pub mod khan;
 pub mod local_config;
+pub mod retry;
+pub mod scheduler;
+pub mod scry;
+pub mod shared_channel;
 pub mod subscription;
 pub mod traits;
 
 pub use apps::collections::{Collection, Link};
+pub use apps::contacts::{Contact, ContactField, ContactUpdate, Contacts, NicknamedMessage};
+pub use apps::docket::{Charge, ChargeState, Docket, DocketHref, DocketMetadata, Treaty};
+pub use apps::feed::{Feed, FeedPost};
 pub use apps::harkstore::HarkStore;
+pub use apps::hood::{Germ, Hood, Vat};
 pub use apps::invitestore::InviteStore;
-pub use apps::notebook::Note;
-pub use channel::Channel;
+pub use apps::notebook::{Note, NoteImport, NoteRevision};
+pub use apps::settings::{Settings, SettingsBucket, SettingsEvent, SettingsValue};
+pub use bot::{Bot, BotErrorHandler, BotHandler, BotMessage};
+pub use channel::{Channel, ChannelBatch, StreamErrorCallback};
 pub use error::{Result, UrbitAPIError};
 pub use graph::{Graph, Node, NodeContents};
 pub use graphstore::Module;
 pub use helper::get_current_da_time;
-pub use interface::ShipInterface;
+pub use interface::{ShipInterface, ShipInterfaceBuilder};
+pub use khan::ThreadHandle;
 pub use local_config::{
     create_new_ship_config_file, default_cli_ship_interface_setup, ship_interface_from_config,
     ship_interface_from_local_config,
 };
-pub use subscription::Subscription;
+pub use retry::{RequestKind, RetryPolicy};
+pub use scheduler::{CronSchedule, ScheduledMessage, Scheduler};
+pub use scry::{FromScry, ScryPath};
+pub use shared_channel::{MessageSubscription, SharedChannel, SharedSubscription};
+pub use subscription::{
+    ErrorCallback, EventOffer, FactCallback, OverflowPolicy, QuitCallback, Subscription,
+    SubscriptionHandle,
+};
 pub use traits::messaging::{AuthoredMessage, Message, Messaging};
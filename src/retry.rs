@@ -0,0 +1,260 @@
+use crate::error::{is_dropped_connection, Result, UrbitAPIError, RETRYABLE_STATUSES};
+use rand::Rng;
+use reqwest::blocking::Response;
+use std::time::Duration;
+
+/// A policy which decides whether (and when) a request made via a `ShipInterface`
+/// is retried after a transient failure, such as the connection being reset or the
+/// ship responding with `503` while it is busy.
+///
+/// Requests which are not idempotent (pokes sent over a channel and spider threads)
+/// could take effect twice if they are retried after reaching the ship, so by default
+/// they are only retried if the connection to the ship could not be established.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts for a request, including the first one.
+    /// A value of 1 disables retrying.
+    pub max_attempts: u32,
+    /// The delay before the first retry
+    pub initial_backoff: Duration,
+    /// The maximum delay between attempts
+    pub max_backoff: Duration,
+    /// The factor the delay is multiplied by after every attempt
+    pub backoff_multiplier: f64,
+    /// Whether to randomize the delays, so that many clients do not retry in lockstep
+    pub jitter: bool,
+    /// The HTTP statuses which are considered transient and thus retried. Defaults to
+    /// the statuses for which `UrbitAPIError::is_retryable` is true, which excludes
+    /// `500` as the ship responds with it when a scry or thread crashes.
+    pub retryable_statuses: Vec<u16>,
+    /// Whether scries are retried
+    pub retry_scries: bool,
+    /// Whether spider threads are retried
+    pub retry_spider: bool,
+    /// Whether channel PUT requests (pokes, subscribes, acks, etc.) are retried
+    pub retry_channel_requests: bool,
+    /// Whether requests which are not idempotent are retried on failures which may
+    /// have happened after the ship received the request (statuses, timeouts and
+    /// dropped connections)
+    pub retry_non_idempotent: bool,
+}
+
+/// The kind of request which is made to a ship, used by a `RetryPolicy` to decide
+/// whether the request may be retried
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RequestKind {
+    Scry,
+    Spider,
+    /// A channel PUT request, which is idempotent unless it includes a poke
+    ChannelRequest {
+        idempotent: bool,
+    },
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(5),
+            backoff_multiplier: 2.0,
+            jitter: true,
+            retryable_statuses: RETRYABLE_STATUSES.to_vec(),
+            retry_scries: true,
+            retry_spider: true,
+            retry_channel_requests: true,
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy which never retries requests
+    pub fn never() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Set the maximum number of attempts for a request, including the first one
+    pub fn max_attempts(mut self, max_attempts: u32) -> RetryPolicy {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Set the delay before the first retry and the maximum delay between attempts
+    pub fn backoff(mut self, initial_backoff: Duration, max_backoff: Duration) -> RetryPolicy {
+        self.initial_backoff = initial_backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Set whether the delays are randomized
+    pub fn jitter(mut self, jitter: bool) -> RetryPolicy {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Set the HTTP statuses which are retried
+    pub fn retryable_statuses(mut self, statuses: &[u16]) -> RetryPolicy {
+        self.retryable_statuses = statuses.to_vec();
+        self
+    }
+
+    /// Set whether requests which failed with `500` are retried. Useful if the ship is
+    /// behind a proxy which responds with `500` on transient failures.
+    pub fn retry_internal_errors(mut self, retry: bool) -> RetryPolicy {
+        self.retryable_statuses.retain(|&status| status != 500);
+        if retry {
+            self.retryable_statuses.push(500);
+        }
+        self
+    }
+
+    /// Set whether requests which are not idempotent (such as pokes) are retried
+    /// on failures which may have happened after the ship received them
+    pub fn retry_non_idempotent(mut self, retry: bool) -> RetryPolicy {
+        self.retry_non_idempotent = retry;
+        self
+    }
+
+    /// The delay before the given retry (where the first retry is 1)
+    pub fn backoff_for(&self, retry: u32) -> Duration {
+        let factor = self
+            .backoff_multiplier
+            .powi(retry.saturating_sub(1).min(i32::MAX as u32) as i32);
+        let backoff = self
+            .initial_backoff
+            .mul_f64(factor.max(0.0).min(u32::MAX as f64))
+            .min(self.max_backoff);
+
+        if self.jitter && !backoff.is_zero() {
+            // Equal jitter: keep half of the backoff and randomize the other half
+            let half = backoff / 2;
+            half + half.mul_f64(rand::thread_rng().gen::<f64>())
+        } else {
+            backoff
+        }
+    }
+
+    /// Whether a request of the given kind which resulted in `result` on the given
+    /// attempt (where the first attempt is 1) should be retried
+    pub fn should_retry(&self, kind: RequestKind, attempt: u32, result: &Result<Response>) -> bool {
+        if attempt >= self.max_attempts {
+            return false;
+        }
+        let (enabled, idempotent) = match kind {
+            RequestKind::Scry => (self.retry_scries, true),
+            RequestKind::Spider => (self.retry_spider, false),
+            RequestKind::ChannelRequest { idempotent } => (self.retry_channel_requests, idempotent),
+        };
+        if !enabled {
+            return false;
+        }
+        let may_repeat = idempotent || self.retry_non_idempotent;
+
+        match result {
+            Ok(resp) => may_repeat && self.retryable_statuses.contains(&resp.status().as_u16()),
+            // The request never reached the ship, so it is always safe to retry
+            Err(UrbitAPIError::ReqwestError(e)) if e.is_connect() => true,
+            // The ship may have received the request before it timed out or the
+            // connection dropped, so it is only retried if it can be repeated
+            Err(UrbitAPIError::ReqwestError(e)) => {
+                may_repeat && (e.is_timeout() || is_dropped_connection(e))
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Verify that the backoff grows exponentially up to the maximum
+    fn backoff_is_exponential_and_capped() {
+        let policy = RetryPolicy::default()
+            .jitter(false)
+            .backoff(Duration::from_millis(100), Duration::from_millis(350));
+        assert_eq!(policy.backoff_for(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff_for(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff_for(3), Duration::from_millis(350));
+
+        let jittered = policy.jitter(true).backoff_for(2);
+        assert!(jittered >= Duration::from_millis(100) && jittered <= Duration::from_millis(200));
+    }
+
+    #[test]
+    // Verify that the default statuses match `UrbitAPIError::is_retryable`
+    fn default_statuses_match_is_retryable() {
+        let policy = RetryPolicy::default();
+        for status in 400..600 {
+            let error = UrbitAPIError::HttpError {
+                status,
+                endpoint: "/~/scry/hood/kiln/pikes.json".to_string(),
+                body: String::new(),
+            };
+            assert_eq!(
+                policy.retryable_statuses.contains(&status),
+                error.is_retryable()
+            );
+        }
+
+        let policy = policy.retry_internal_errors(true);
+        assert!(policy.retryable_statuses.contains(&500));
+        assert!(!policy
+            .retry_internal_errors(false)
+            .retryable_statuses
+            .contains(&500));
+    }
+
+    #[test]
+    // Verify that a connection reset while waiting for the response is retried only
+    // if the request can be repeated, and that refused connections are always retried
+    fn classifies_dropped_connections() {
+        use std::io::Read;
+        use std::net::TcpListener;
+        use std::thread;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // Read part of the request and close the connection with unread data,
+            // which makes the kernel reset it
+            let mut buf = [0; 8];
+            let _ = stream.read(&mut buf);
+            thread::sleep(Duration::from_millis(100));
+        });
+        let client = reqwest::blocking::Client::new();
+        let reset: Result<Response> = client
+            .put(&url)
+            .body(vec![b'x'; 4096])
+            .send()
+            .map_err(UrbitAPIError::from);
+        server.join().unwrap();
+        let refused: Result<Response> = client.get(&url).send().map_err(UrbitAPIError::from);
+
+        let policy = RetryPolicy::default();
+        let poke = RequestKind::ChannelRequest { idempotent: false };
+        match &reset {
+            Err(error @ UrbitAPIError::ReqwestError(e)) => {
+                assert!(is_dropped_connection(e));
+                assert!(error.is_retryable());
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert!(policy.should_retry(RequestKind::Scry, 1, &reset));
+        assert!(!policy.should_retry(poke, 1, &reset));
+        assert!(policy
+            .clone()
+            .retry_non_idempotent(true)
+            .should_retry(poke, 1, &reset));
+
+        assert!(refused.as_ref().err().map_or(false, |e| e.is_retryable()));
+        assert!(policy.should_retry(poke, 1, &refused));
+        assert!(!policy.should_retry(RequestKind::Scry, 1, &Err(UrbitAPIError::FailedToLogin)));
+    }
+}
@@ -0,0 +1,548 @@
+use crate::error::{Result, UrbitAPIError};
+use crate::helper::da_to_unix_time;
+use crate::interface::ShipInterface;
+use crate::traits::messaging::{Message, Messaging};
+use chrono::prelude::*;
+use chrono::Duration as ChronoDuration;
+use json::{object, JsonValue};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// The longest the `Scheduler` sleeps between checks for due messages while running
+static MAX_SCHEDULER_SLEEP_SECS: i64 = 60;
+
+/// A cron-like recurrence schedule, made up of the five standard fields
+/// `minute hour day-of-month month day-of-week`. Each field supports `*`,
+/// numbers, lists (`1,15`), ranges (`1-5`) and steps (`*/15`, `0-30/10`).
+/// Day-of-week uses `0` (or `7`) for Sunday. All times are in UTC.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CronSchedule {
+    expression: String,
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days_of_month: Vec<u32>,
+    months: Vec<u32>,
+    days_of_week: Vec<u32>,
+    /// Whether the day-of-month field was `*`
+    any_day_of_month: bool,
+    /// Whether the day-of-week field was `*`
+    any_day_of_week: bool,
+}
+
+/// A `Message` which is queued to be sent to a chat/DM by a `Scheduler`
+#[derive(Clone, Debug)]
+pub struct ScheduledMessage {
+    /// The id of the scheduled message, unique within its `Scheduler`
+    pub id: u64,
+    /// The ship hosting the chat/DM
+    pub resource_ship: String,
+    /// The name of the chat/DM
+    pub resource_name: String,
+    pub message: Message,
+    /// The next time the message will be sent
+    pub next_send: DateTime<Utc>,
+    /// The recurrence of the message, or `None` if it is only sent once
+    pub recurrence: Option<CronSchedule>,
+}
+
+/// A queue of scheduled and recurring messages which is persisted to a json file,
+/// thereby surviving restarts. Messages are sent via `Messaging::send_message`.
+#[derive(Debug)]
+pub struct Scheduler {
+    /// The path of the json file which the queue is persisted to
+    pub path: PathBuf,
+    queue: Vec<ScheduledMessage>,
+    next_id: u64,
+}
+
+impl CronSchedule {
+    /// Parses a cron expression such as `30 9 * * 1-5` (09:30 UTC every weekday)
+    pub fn parse(expression: &str) -> Result<CronSchedule> {
+        let invalid = || UrbitAPIError::InvalidCronSchedule(expression.to_string());
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(invalid());
+        }
+
+        let mut days_of_week = parse_cron_field(fields[4], 0, 7).ok_or_else(invalid)?;
+        // Sunday may be either 0 or 7
+        for day in days_of_week.iter_mut() {
+            if *day == 7 {
+                *day = 0;
+            }
+        }
+        days_of_week.sort_unstable();
+        days_of_week.dedup();
+
+        Ok(CronSchedule {
+            expression: expression.to_string(),
+            minutes: parse_cron_field(fields[0], 0, 59).ok_or_else(invalid)?,
+            hours: parse_cron_field(fields[1], 0, 23).ok_or_else(invalid)?,
+            days_of_month: parse_cron_field(fields[2], 1, 31).ok_or_else(invalid)?,
+            months: parse_cron_field(fields[3], 1, 12).ok_or_else(invalid)?,
+            days_of_week,
+            any_day_of_month: fields[2] == "*",
+            any_day_of_week: fields[4] == "*",
+        })
+    }
+
+    /// The cron expression the schedule was parsed from
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// Finds the first time strictly after `after` which matches the schedule.
+    /// Returns `None` if no such time exists within the next 5 years (ex. `0 0 31 2 *`).
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+        let mut day = start.date_naive();
+        let last_day = day + ChronoDuration::days(366 * 5);
+
+        while day <= last_day {
+            if self.months.contains(&day.month()) && self.matches_day(&day) {
+                for &hour in &self.hours {
+                    for &minute in &self.minutes {
+                        let candidate = Utc.from_utc_datetime(&day.and_hms_opt(hour, minute, 0)?);
+                        if candidate >= start {
+                            return Some(candidate);
+                        }
+                    }
+                }
+            }
+            day = day.succ_opt()?;
+        }
+        None
+    }
+
+    /// Checks whether the day of month/week fields match the day. As in cron, if both
+    /// fields are restricted then matching either of them is sufficient.
+    fn matches_day(&self, day: &NaiveDate) -> bool {
+        let dom = self.days_of_month.contains(&day.day());
+        let dow = self
+            .days_of_week
+            .contains(&day.weekday().num_days_from_sunday());
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (true, true) => true,
+            (true, false) => dow,
+            (false, true) => dom,
+            (false, false) => dom || dow,
+        }
+    }
+}
+
+impl ScheduledMessage {
+    /// Converts to `JsonValue` for persisting
+    pub fn to_json(&self) -> JsonValue {
+        object! {
+            "id": self.id,
+            "resource-ship": self.resource_ship.clone(),
+            "resource-name": self.resource_name.clone(),
+            "contents": self.message.to_json(),
+            "next-send": self.next_send.timestamp_millis(),
+            "recurrence": self.recurrence.as_ref().map(|r| r.expression().to_string())
+        }
+    }
+
+    /// Convert from a persisted `JsonValue` to a `ScheduledMessage`
+    pub fn from_json(json: &JsonValue) -> Result<ScheduledMessage> {
+        let invalid = || UrbitAPIError::FailedToLoadSchedule(json.dump());
+        let next_send_millis = json["next-send"].as_i64().ok_or_else(invalid)?;
+        let recurrence = match json["recurrence"].as_str() {
+            Some(expression) => Some(CronSchedule::parse(expression)?),
+            None => None,
+        };
+
+        Ok(ScheduledMessage {
+            id: json["id"].as_u64().ok_or_else(invalid)?,
+            resource_ship: json["resource-ship"]
+                .as_str()
+                .ok_or_else(invalid)?
+                .to_string(),
+            resource_name: json["resource-name"]
+                .as_str()
+                .ok_or_else(invalid)?
+                .to_string(),
+            message: Message::from_json(json["contents"].members().cloned().collect()),
+            next_send: Utc
+                .timestamp_millis_opt(next_send_millis)
+                .single()
+                .ok_or_else(invalid)?,
+            recurrence,
+        })
+    }
+}
+
+impl Scheduler {
+    /// Loads the queue persisted at `path`, or creates a new empty `Scheduler`
+    /// which will persist to `path` if no file exists there yet.
+    pub fn load_or_create(path: &str) -> Result<Scheduler> {
+        let mut scheduler = Scheduler {
+            path: PathBuf::from(path),
+            queue: vec![],
+            next_id: 1,
+        };
+        if !Path::new(path).exists() {
+            return Ok(scheduler);
+        }
+
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| UrbitAPIError::FailedToLoadSchedule(format!("{}: {}", path, e)))?;
+        let json = json::parse(&contents)
+            .map_err(|e| UrbitAPIError::FailedToLoadSchedule(format!("{}: {}", path, e)))?;
+        for scheduled_json in json["queue"].members() {
+            scheduler
+                .queue
+                .push(ScheduledMessage::from_json(scheduled_json)?);
+        }
+        scheduler.next_id = json["next-id"]
+            .as_u64()
+            .unwrap_or(1)
+            .max(scheduler.queue.iter().map(|s| s.id + 1).max().unwrap_or(1));
+
+        Ok(scheduler)
+    }
+
+    /// The messages which are currently queued, ordered by when they will next be sent
+    pub fn pending(&self) -> Vec<&ScheduledMessage> {
+        let mut pending: Vec<&ScheduledMessage> = self.queue.iter().collect();
+        pending.sort_by_key(|s| s.next_send);
+        pending
+    }
+
+    /// Queue a message to be sent once to a chat/DM at the given time.
+    /// Returns the id of the scheduled message.
+    pub fn schedule_at(
+        &mut self,
+        resource_ship: &str,
+        resource_name: &str,
+        message: &Message,
+        send_time: DateTime<Utc>,
+    ) -> Result<u64> {
+        self.push(resource_ship, resource_name, message, send_time, None)
+    }
+
+    /// Queue a message to be sent once to a chat/DM at the given `@da` time.
+    /// Returns the id of the scheduled message.
+    pub fn schedule_at_da(
+        &mut self,
+        resource_ship: &str,
+        resource_name: &str,
+        message: &Message,
+        send_time_da: u128,
+    ) -> Result<u64> {
+        let unix_time = da_to_unix_time(send_time_da) as i64;
+        let send_time = Utc
+            .timestamp_millis_opt(unix_time)
+            .single()
+            .ok_or_else(|| UrbitAPIError::Other(format!("Invalid @da time {}", send_time_da)))?;
+        self.schedule_at(resource_ship, resource_name, message, send_time)
+    }
+
+    /// Queue a message to be sent to a chat/DM repeatedly, following a cron expression
+    /// (see `CronSchedule`). Returns the id of the scheduled message.
+    pub fn schedule_recurring(
+        &mut self,
+        resource_ship: &str,
+        resource_name: &str,
+        message: &Message,
+        cron_expression: &str,
+    ) -> Result<u64> {
+        let recurrence = CronSchedule::parse(cron_expression)?;
+        let first_send = recurrence
+            .next_after(Utc::now())
+            .ok_or_else(|| UrbitAPIError::InvalidCronSchedule(cron_expression.to_string()))?;
+        self.push(
+            resource_ship,
+            resource_name,
+            message,
+            first_send,
+            Some(recurrence),
+        )
+    }
+
+    /// Remove a message from the queue. Returns whether a message with the id was queued.
+    pub fn cancel(&mut self, id: u64) -> Result<bool> {
+        let queue_len = self.queue.len();
+        self.queue.retain(|s| s.id != id);
+        if self.queue.len() == queue_len {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Sends every queued message which is due using the provided `Messaging` interface.
+    /// One-off messages are removed from the queue once sent, and recurring messages are
+    /// rescheduled. Returns the ids of the messages which were sent.
+    ///
+    /// The queue is persisted after every sent message, so if sending fails part-way
+    /// the messages which were already sent are not sent again.
+    pub fn send_due(&mut self, messaging: &mut impl Messaging) -> Result<Vec<u64>> {
+        let now = Utc::now();
+        let due: Vec<u64> = self
+            .queue
+            .iter()
+            .filter(|s| s.next_send <= now)
+            .map(|s| s.id)
+            .collect();
+
+        let mut sent = vec![];
+        for id in due {
+            let index = match self.queue.iter().position(|s| s.id == id) {
+                Some(index) => index,
+                None => continue,
+            };
+            let scheduled = &self.queue[index];
+            messaging.send_message(
+                &scheduled.resource_ship,
+                &scheduled.resource_name,
+                &scheduled.message,
+            )?;
+            sent.push(id);
+
+            // Reschedule recurring messages, skipping any occurrences which were missed
+            match scheduled
+                .recurrence
+                .as_ref()
+                .and_then(|r| r.next_after(now))
+            {
+                Some(next_send) => self.queue[index].next_send = next_send,
+                None => {
+                    self.queue.remove(index);
+                }
+            }
+            self.save()?;
+        }
+        Ok(sent)
+    }
+
+    /// Runs the scheduler, sending queued messages as they become due until the queue is
+    /// empty. Blocks the current thread. Messages which became due while the scheduler was
+    /// not running are sent immediately.
+    pub fn run(&mut self, ship_interface: &ShipInterface) -> Result<()> {
+        let mut channel = ship_interface.create_channel()?;
+        while !self.queue.is_empty() {
+            let res = self.send_due(&mut channel.chat());
+            if res.is_err() {
+                channel.delete_channel();
+                return res.map(|_| ());
+            }
+
+            // Sleep until the next message is due
+            let until_next = self
+                .queue
+                .iter()
+                .map(|s| s.next_send - Utc::now())
+                .min()
+                .unwrap_or_else(ChronoDuration::zero)
+                .num_milliseconds()
+                .clamp(0, MAX_SCHEDULER_SLEEP_SECS * 1000);
+            thread::sleep(Duration::from_millis(until_next as u64));
+        }
+        channel.delete_channel();
+        Ok(())
+    }
+
+    /// Persists the queue to the json file at `self.path`
+    pub fn save(&self) -> Result<()> {
+        let queue: Vec<JsonValue> = self.queue.iter().map(|s| s.to_json()).collect();
+        let json = object! {
+            "next-id": self.next_id,
+            "queue": queue
+        };
+        let persist_err = |e: std::io::Error| {
+            UrbitAPIError::FailedToPersistSchedule(format!("{}: {}", self.path.display(), e))
+        };
+
+        // Write to a temporary file first so that a crash can not truncate the queue
+        let mut temp_path = self.path.clone().into_os_string();
+        temp_path.push(".tmp");
+        std::fs::write(&temp_path, json.pretty(2)).map_err(persist_err)?;
+        std::fs::rename(&temp_path, &self.path).map_err(persist_err)
+    }
+
+    /// Internal method which adds a message to the queue and persists it
+    fn push(
+        &mut self,
+        resource_ship: &str,
+        resource_name: &str,
+        message: &Message,
+        next_send: DateTime<Utc>,
+        recurrence: Option<CronSchedule>,
+    ) -> Result<u64> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.queue.push(ScheduledMessage {
+            id,
+            resource_ship: resource_ship.to_string(),
+            resource_name: resource_name.to_string(),
+            message: message.clone(),
+            next_send,
+            recurrence,
+        });
+        self.save()?;
+        Ok(id)
+    }
+}
+
+/// Parses a single cron field into the sorted list of values it matches
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Option<Vec<u32>> {
+    let mut values = vec![];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)?),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (start.parse().ok()?, end.parse().ok()?)
+        } else {
+            let value = range.parse().ok()?;
+            // A single value with a step (ex. `5/15`) runs until the maximum
+            (value, if part.contains('/') { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            return None;
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+    values.sort_unstable();
+    values.dedup();
+    Some(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Verify that cron expressions are parsed and invalid ones rejected
+    fn can_parse_cron_schedule() {
+        let schedule = CronSchedule::parse("*/15 9-17 1,15 * 7").unwrap();
+        assert_eq!(schedule.minutes, vec![0, 15, 30, 45]);
+        assert_eq!(schedule.hours, (9..=17).collect::<Vec<u32>>());
+        assert_eq!(schedule.days_of_month, vec![1, 15]);
+        assert_eq!(schedule.months, (1..=12).collect::<Vec<u32>>());
+        assert_eq!(schedule.days_of_week, vec![0]);
+        assert_eq!(schedule.expression(), "*/15 9-17 1,15 * 7");
+
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("* * 0 * *").is_err());
+        assert!(CronSchedule::parse("a * * * *").is_err());
+    }
+
+    #[test]
+    // Verify that the next matching time is found
+    fn can_find_next_cron_time() {
+        let after = Utc.with_ymd_and_hms(2021, 6, 4, 10, 20, 30).unwrap();
+
+        // 09:30 every weekday, where 2021-06-04 is a Friday
+        let weekdays = CronSchedule::parse("30 9 * * 1-5").unwrap();
+        assert_eq!(
+            weekdays.next_after(after),
+            Some(Utc.with_ymd_and_hms(2021, 6, 7, 9, 30, 0).unwrap())
+        );
+
+        // Strictly after, even when `after` matches exactly
+        let hourly = CronSchedule::parse("0 * * * *").unwrap();
+        let on_the_hour = Utc.with_ymd_and_hms(2021, 6, 4, 11, 0, 0).unwrap();
+        assert_eq!(
+            hourly.next_after(on_the_hour),
+            Some(Utc.with_ymd_and_hms(2021, 6, 4, 12, 0, 0).unwrap())
+        );
+
+        // Either the day of month or the day of week matching is sufficient
+        let either = CronSchedule::parse("0 0 13 * 6").unwrap();
+        assert_eq!(
+            either.next_after(after),
+            Some(Utc.with_ymd_and_hms(2021, 6, 5, 0, 0, 0).unwrap())
+        );
+
+        // February 31st never exists
+        let never = CronSchedule::parse("0 0 31 2 *").unwrap();
+        assert_eq!(never.next_after(after), None);
+    }
+
+    #[test]
+    // Verify that the queue survives being persisted to json and loaded again
+    fn can_persist_and_load_queue() {
+        let path = crate::helper::unique_temp_path("schedule.json");
+        let path_str = path.to_str().unwrap();
+        let message = Message::new().add_text("hello");
+        let send_time = Utc.with_ymd_and_hms(2031, 6, 4, 9, 30, 0).unwrap();
+
+        let mut scheduler = Scheduler::load_or_create(path_str).unwrap();
+        let once = scheduler
+            .schedule_at("~zod", "chat-1", &message, send_time)
+            .unwrap();
+        let recurring = scheduler
+            .schedule_recurring("~bus", "dm-2", &message, "30 9 * * 1-5")
+            .unwrap();
+        let cancelled = scheduler
+            .schedule_at("~zod", "chat-1", &message, send_time)
+            .unwrap();
+        assert!(scheduler.cancel(cancelled).unwrap());
+
+        let mut loaded = Scheduler::load_or_create(path_str).unwrap();
+        let original: Vec<String> = scheduler
+            .pending()
+            .iter()
+            .map(|s| s.to_json().dump())
+            .collect();
+        let reloaded: Vec<String> = loaded
+            .pending()
+            .iter()
+            .map(|s| s.to_json().dump())
+            .collect();
+        assert_eq!(original, reloaded);
+
+        let loaded_once = loaded.pending().into_iter().find(|s| s.id == once).unwrap();
+        assert_eq!(loaded_once.resource_ship, "~zod");
+        assert_eq!(loaded_once.resource_name, "chat-1");
+        assert_eq!(loaded_once.next_send, send_time);
+        assert_eq!(loaded_once.recurrence, None);
+        let loaded_recurring = loaded
+            .pending()
+            .into_iter()
+            .find(|s| s.id == recurring)
+            .unwrap();
+        assert_eq!(
+            loaded_recurring.recurrence,
+            Some(CronSchedule::parse("30 9 * * 1-5").unwrap())
+        );
+
+        // Ids of cancelled messages are not handed out again after loading
+        let next = loaded
+            .schedule_at("~zod", "chat-1", &message, send_time)
+            .unwrap();
+        assert!(next > cancelled);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    // Verify that a missing file gives an empty queue, and a corrupt one an error
+    fn can_load_missing_or_corrupt_queue() {
+        let path = crate::helper::unique_temp_path("schedule.json");
+        let path_str = path.to_str().unwrap();
+
+        let scheduler = Scheduler::load_or_create(path_str).unwrap();
+        assert!(scheduler.pending().is_empty());
+        assert!(!path.exists());
+
+        std::fs::write(&path, "{\"queue\": [").unwrap();
+        match Scheduler::load_or_create(path_str) {
+            Err(UrbitAPIError::FailedToLoadSchedule(_)) => {}
+            other => panic!("Expected FailedToLoadSchedule, got {:?}", other),
+        }
+
+        std::fs::write(&path, r#"{"next-id": 2, "queue": [{"id": 1}]}"#).unwrap();
+        match Scheduler::load_or_create(path_str) {
+            Err(UrbitAPIError::FailedToLoadSchedule(_)) => {}
+            other => panic!("Expected FailedToLoadSchedule, got {:?}", other),
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
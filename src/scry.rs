@@ -0,0 +1,212 @@
+use crate::error::{Result, UrbitAPIError};
+use crate::graph::Graph;
+use crate::helper::{da_to_string, index_dec_to_ud};
+use json::JsonValue;
+use std::fmt;
+
+/// A type which can be decoded from the json result of a scry, used by
+/// `ShipInterface::scry_typed`.
+pub trait FromScry: Sized {
+    /// Decode the json returned by the scry
+    fn from_scry(json: &JsonValue) -> Result<Self>;
+}
+
+/// A builder for scry paths which encodes every segment with the correct aura,
+/// escaping any characters which are not allowed in a path.
+///
+/// ```ignore
+/// // /graph/~zod/chat-1/node/1.000
+/// let path = ScryPath::new()
+///     .knot("graph")
+///     .ship("~zod")
+///     .knot("chat-1")
+///     .knot("node")
+///     .ud(1000);
+/// let graph: Graph = ship_interface.scry_typed("graph-store", path.as_str())?;
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScryPath {
+    path: String,
+}
+
+impl ScryPath {
+    /// Create an empty scry path
+    pub fn new() -> ScryPath {
+        ScryPath::default()
+    }
+
+    /// Append a `@ta` segment. Simple segments such as `keys` are kept as is,
+    /// while any characters not allowed in a path are escaped.
+    pub fn knot(self, segment: &str) -> ScryPath {
+        let segment = wood(segment);
+        self.push(&segment)
+    }
+
+    /// Append a `@t` (text) segment, which may contain any characters
+    pub fn text(self, segment: &str) -> ScryPath {
+        let segment = format!("~~{}", wood(segment));
+        self.push(&segment)
+    }
+
+    /// Append a `@p` segment, with or without the leading `~`
+    pub fn ship(self, ship: &str) -> ScryPath {
+        let segment = format!("~{}", ship.trim_start_matches('~'));
+        self.push(&segment)
+    }
+
+    /// Append a `@ud` segment, such as `1.000` for 1000
+    pub fn ud(self, number: u128) -> ScryPath {
+        let segment = index_dec_to_ud(&number.to_string());
+        self.push(segment.trim_start_matches('/'))
+    }
+
+    /// Append a `@da` segment from a time in `@da` encoding
+    /// (see `helper::unix_time_to_da`)
+    pub fn da(self, da: u128) -> ScryPath {
+        let segment = da_to_string(da);
+        self.push(&segment)
+    }
+
+    /// Append an already encoded segment (or several, separated by `/`) as is
+    pub fn raw(self, segments: &str) -> ScryPath {
+        self.push(segments.trim_matches('/'))
+    }
+
+    /// The encoded path, starting with a `/`
+    pub fn as_str(&self) -> &str {
+        &self.path
+    }
+
+    /// Internal method which appends an encoded segment
+    fn push(mut self, segment: &str) -> ScryPath {
+        self.path += "/";
+        self.path += segment;
+        self
+    }
+}
+
+impl fmt::Display for ScryPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.path)
+    }
+}
+
+impl AsRef<str> for ScryPath {
+    fn as_ref(&self) -> &str {
+        &self.path
+    }
+}
+
+/// Escape text so that it only contains characters allowed in a `@ta`, in the
+/// same way as `+wood` in Hoon (which is reversed by `+woad`).
+fn wood(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            'a'..='z' | '0'..='9' | '-' => escaped.push(c),
+            ' ' => escaped.push('.'),
+            '.' => escaped.push_str("~."),
+            '~' => escaped.push_str("~~"),
+            _ => escaped += &format!("~{:x}.", c as u32),
+        }
+    }
+    escaped
+}
+
+impl FromScry for JsonValue {
+    fn from_scry(json: &JsonValue) -> Result<Self> {
+        Ok(json.clone())
+    }
+}
+
+impl FromScry for String {
+    fn from_scry(json: &JsonValue) -> Result<Self> {
+        json.as_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| UrbitAPIError::FailedToDecodeScry(json.dump()))
+    }
+}
+
+impl FromScry for bool {
+    fn from_scry(json: &JsonValue) -> Result<Self> {
+        json.as_bool()
+            .ok_or_else(|| UrbitAPIError::FailedToDecodeScry(json.dump()))
+    }
+}
+
+impl FromScry for u64 {
+    fn from_scry(json: &JsonValue) -> Result<Self> {
+        json.as_u64()
+            .ok_or_else(|| UrbitAPIError::FailedToDecodeScry(json.dump()))
+    }
+}
+
+impl FromScry for i64 {
+    fn from_scry(json: &JsonValue) -> Result<Self> {
+        json.as_i64()
+            .ok_or_else(|| UrbitAPIError::FailedToDecodeScry(json.dump()))
+    }
+}
+
+impl FromScry for f64 {
+    fn from_scry(json: &JsonValue) -> Result<Self> {
+        json.as_f64()
+            .ok_or_else(|| UrbitAPIError::FailedToDecodeScry(json.dump()))
+    }
+}
+
+impl<T: FromScry> FromScry for Option<T> {
+    fn from_scry(json: &JsonValue) -> Result<Self> {
+        if json.is_null() {
+            Ok(None)
+        } else {
+            T::from_scry(json).map(Some)
+        }
+    }
+}
+
+impl<T: FromScry> FromScry for Vec<T> {
+    fn from_scry(json: &JsonValue) -> Result<Self> {
+        if !json.is_array() {
+            return Err(UrbitAPIError::FailedToDecodeScry(json.dump()));
+        }
+        json.members().map(T::from_scry).collect()
+    }
+}
+
+impl FromScry for Graph {
+    fn from_scry(json: &JsonValue) -> Result<Self> {
+        Graph::from_json(json.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::unix_time_to_da;
+
+    #[test]
+    // Verify that path segments are encoded with the correct auras
+    fn can_build_scry_path() {
+        let path = ScryPath::new()
+            .knot("graph")
+            .ship("zod")
+            .text("Hello World.")
+            .ud(1234567)
+            .da(unix_time_to_da(1622729109500));
+        assert_eq!(
+            path.as_str(),
+            "/graph/~zod/~~~48.ello.~57.orld~./1.234.567/~2021.6.3..14.05.09..8000"
+        );
+        assert_eq!(ScryPath::new().raw("/keys/").to_string(), "/keys");
+    }
+
+    #[test]
+    // Verify that characters not allowed in a `@ta` are escaped like `+wood`
+    fn can_escape_knots() {
+        assert_eq!(wood("chat-1"), "chat-1");
+        assert_eq!(wood("my_Chat"), "my~5f.~43.hat");
+        assert_eq!(wood("a.b~c d"), "a~.b~~c.d");
+        assert_eq!(ScryPath::new().knot("A_B").as_str(), "/~41.~5f.~42.");
+    }
+}
@@ -0,0 +1,572 @@
+use crate::channel::StreamErrorCallback;
+use crate::error::{Result, UrbitAPIError};
+use crate::event_stream::{open_event_stream, EventReceiver};
+use crate::graph::Node;
+use crate::interface::ShipInterface;
+use crate::subscription::CreationID;
+use crate::traits::messaging::{check_resource_json, AuthoredMessage};
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use eventsource_threaded::event::Event;
+use json::{object, JsonValue};
+use rand::Rng;
+use reqwest::blocking::Response;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::SystemTime;
+
+/// A thread-safe handle to a channel with a ship, which can be cloned and shared
+/// freely between threads (it is `Send + Sync`).
+///
+/// Unlike `Channel`, a `SharedChannel` owns a single background thread which reads
+/// the channel's SSE stream and immediately dispatches each event to the `Receiver`
+/// of the subscription it belongs to. This allows any number of subscriptions to
+/// share one connection to the ship without any polling.
+///
+/// The channel is deleted on the ship once every handle to it has been dropped
+/// (or when `delete_channel` is called).
+#[derive(Clone, Debug)]
+pub struct SharedChannel {
+    inner: Arc<SharedChannelInner>,
+}
+
+/// A subscription created via a `SharedChannel`
+#[derive(Clone, Debug)]
+pub struct SharedSubscription {
+    /// The id of the message that created this subscription
+    pub creation_id: CreationID,
+    /// The app that is being subscribed to
+    pub app: String,
+    /// The path of the app being subscribed to
+    pub path: String,
+    /// Receives the json of every event of the subscription as soon as it arrives.
+    /// Disconnects once the ship ends the subscription or it is unsubscribed from.
+    pub receiver: Receiver<JsonValue>,
+}
+
+/// A subscription to the messages of a chat/DM created via
+/// `SharedChannel::subscribe_to_messages`. Stops receiving messages once dropped.
+#[derive(Debug)]
+pub struct MessageSubscription {
+    /// Receives every `AuthoredMessage` posted to the chat/DM after subscribing
+    pub receiver: Receiver<AuthoredMessage>,
+    watcher_id: u64,
+    channel: SharedChannel,
+}
+
+/// The state of a `SharedChannel` which is shared between all of its handles
+/// and the background reader thread
+struct SharedChannelInner {
+    ship_interface: ShipInterface,
+    uid: String,
+    url: String,
+    /// The id of the next action that is sent via the channel
+    message_id_count: AtomicU64,
+    /// The senders for every live subscription, keyed by their creation id
+    subscriptions: Mutex<HashMap<CreationID, SubscriptionSender>>,
+    /// The graph-store subscription which is shared by every `MessageSubscription`
+    message_feed: Mutex<MessageFeed>,
+    /// The callback for errors of the event stream
+    on_stream_error: Mutex<Option<StreamErrorCallback>>,
+}
+
+/// A single graph-store `/updates` subscription which is fanned out to the
+/// `MessageSubscription`s of the channel
+#[derive(Debug, Default)]
+struct MessageFeed {
+    /// The creation id of the graph-store subscription, if it is live
+    creation_id: Option<CreationID>,
+    watchers: HashMap<u64, MessageWatcher>,
+    next_watcher_id: u64,
+}
+
+/// The chat/DM a `MessageSubscription` is watching and the sender for its messages
+#[derive(Debug)]
+struct MessageWatcher {
+    resource_ship: String,
+    resource_name: String,
+    sender: Sender<AuthoredMessage>,
+}
+
+/// The sending end of a `SharedSubscription`
+#[derive(Debug)]
+struct SubscriptionSender {
+    sender: Sender<JsonValue>,
+    /// Whether whole events (including the mark) are sent rather than just their json
+    raw_events: bool,
+}
+
+impl SharedChannel {
+    /// Create a new shared channel and spawn the background thread which reads its events
+    pub fn new(ship_interface: ShipInterface) -> Result<SharedChannel> {
+        let mut rng = rand::thread_rng();
+        // Defining the uid as UNIX time, or random if error
+        let uid = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(n) => n.as_micros(),
+            Err(_) => rng.gen(),
+        }
+        .to_string();
+        let url = format!("{}/~/channel/{}", &ship_interface.url, uid);
+
+        let inner = Arc::new(SharedChannelInner {
+            ship_interface,
+            uid,
+            url,
+            message_id_count: AtomicU64::new(1),
+            subscriptions: Mutex::new(HashMap::new()),
+            message_feed: Mutex::new(MessageFeed::default()),
+            on_stream_error: Mutex::new(None),
+        });
+
+        // Open the channel on the ship, which must be done before reading events
+        let resp = inner.send_action(object! {
+            "action": "poke",
+            "ship": inner.ship_interface.ship_name.clone(),
+            "app": "hood",
+            "mark": "helm-hi",
+            "json": "Opening channel",
+        })?;
+        if resp.status().as_u16() != 204 {
+            return Err(UrbitAPIError::from_response(resp));
+        }
+
+        let event_receiver = open_event_stream(&inner.ship_interface, &inner.url);
+
+        let weak_inner = Arc::downgrade(&inner);
+        thread::spawn(move || read_events(event_receiver, weak_inner));
+
+        Ok(SharedChannel { inner })
+    }
+
+    /// The `ShipInterface` this channel was created from
+    pub fn ship_interface(&self) -> &ShipInterface {
+        &self.inner.ship_interface
+    }
+
+    /// The uid of the channel
+    pub fn uid(&self) -> &str {
+        &self.inner.uid
+    }
+
+    /// The url of the channel
+    pub fn url(&self) -> &str {
+        &self.inner.url
+    }
+
+    /// Sends a poke over the channel
+    pub fn poke(&self, app: &str, mark: &str, json: &JsonValue) -> Result<Response> {
+        self.inner.send_action(object! {
+            "action": "poke",
+            "ship": self.inner.ship_interface.ship_name.clone(),
+            "app": app,
+            "mark": mark,
+            "json": json.clone(),
+        })
+    }
+
+    /// Subscribes to events on the ship with the provided app/path. The events are
+    /// delivered to the `receiver` of the returned `SharedSubscription`.
+    pub fn subscribe(&self, app: &str, path: &str) -> Result<SharedSubscription> {
+        self.subscribe_inner(app, path, false)
+    }
+
+    /// Subscribes to events on the ship like `subscribe`, however the `receiver` gets
+    /// the whole event json (including the `mark` of each fact) rather than just its json.
+    pub fn subscribe_raw(&self, app: &str, path: &str) -> Result<SharedSubscription> {
+        self.subscribe_inner(app, path, true)
+    }
+
+    /// Internal method which creates a subscription
+    fn subscribe_inner(
+        &self,
+        app: &str,
+        path: &str,
+        raw_events: bool,
+    ) -> Result<SharedSubscription> {
+        let creation_id = self.inner.next_message_id();
+        let (s, r) = unbounded();
+        // Register the sender before subscribing so that no early events are missed
+        self.inner.subscriptions()?.insert(
+            creation_id,
+            SubscriptionSender {
+                sender: s,
+                raw_events,
+            },
+        );
+
+        let body = json::array![object! {
+            "id": creation_id,
+            "action": "subscribe",
+            "ship": self.inner.ship_interface.ship_name.clone(),
+            "app": app,
+            "path": path,
+        }];
+        let resp = self
+            .inner
+            .ship_interface
+            .send_put_request(&self.inner.url, &body);
+
+        match resp {
+            Ok(resp) if resp.status().as_u16() == 204 => Ok(SharedSubscription {
+                creation_id,
+                app: app.to_string(),
+                path: path.to_string(),
+                receiver: r,
+            }),
+            Ok(resp) => {
+                self.inner.subscriptions()?.remove(&creation_id);
+                Err(UrbitAPIError::from_response(resp))
+            }
+            Err(e) => {
+                self.inner.subscriptions()?.remove(&creation_id);
+                Err(e)
+            }
+        }
+    }
+
+    /// Tells the ship that you are unsubscribing from the subscription with the given
+    /// creation id, and disconnects its `receiver`.
+    pub fn unsubscribe(&self, creation_id: CreationID) -> Result<()> {
+        self.inner.subscriptions()?.remove(&creation_id);
+        self.inner.send_action(object! {
+            "action": "unsubscribe",
+            "subscription": creation_id,
+        })?;
+        Ok(())
+    }
+
+    /// Subscribe to and watch for messages in a chat/DM. The returned `MessageSubscription`
+    /// receives the `AuthoredMessage`s that are posted after subscribing.
+    ///
+    /// This is the `SharedChannel` equivalent of `Messaging::subscribe_to_messages`, however
+    /// all `MessageSubscription`s of the channel share a single graph-store subscription,
+    /// which is ended once the last of them has been dropped.
+    pub fn subscribe_to_messages(
+        &self,
+        resource_ship: &str,
+        resource_name: &str,
+    ) -> Result<MessageSubscription> {
+        let mut feed = self.inner.message_feed()?;
+        if feed.creation_id.is_none() {
+            let subscription = self.subscribe("graph-store", "/updates")?;
+            feed.creation_id = Some(subscription.creation_id);
+            let weak_inner = Arc::downgrade(&self.inner);
+            thread::spawn(move || fan_out_messages(subscription, weak_inner));
+        }
+
+        let (s, r) = unbounded();
+        let watcher_id = feed.next_watcher_id;
+        feed.next_watcher_id += 1;
+        feed.watchers.insert(
+            watcher_id,
+            MessageWatcher {
+                resource_ship: resource_ship.to_string(),
+                resource_name: resource_name.to_string(),
+                sender: s,
+            },
+        );
+        Ok(MessageSubscription {
+            receiver: r,
+            watcher_id,
+            channel: self.clone(),
+        })
+    }
+
+    /// Registers a callback which is called with the error whenever the event stream of
+    /// the channel fails. The stream reconnects by itself, so these errors are otherwise
+    /// ignored.
+    pub fn on_stream_error<F>(&self, callback: F) -> Result<()>
+    where
+        F: FnMut(&UrbitAPIError) + Send + 'static,
+    {
+        let mut on_stream_error = self.inner.on_stream_error.lock().map_err(|_| {
+            UrbitAPIError::Other("Shared channel error callback poisoned".to_string())
+        })?;
+        *on_stream_error = Some(Box::new(callback));
+        Ok(())
+    }
+
+    /// Deletes the channel on the ship, thereby ending all of its subscriptions
+    pub fn delete_channel(&self) -> Result<()> {
+        if let Ok(mut subscriptions) = self.inner.subscriptions.lock() {
+            subscriptions.clear();
+        }
+        if let Ok(mut feed) = self.inner.message_feed.lock() {
+            feed.creation_id = None;
+            feed.watchers.clear();
+        }
+        self.inner.send_action(object! {
+            "action": "delete",
+        })?;
+        Ok(())
+    }
+}
+
+impl SharedChannelInner {
+    /// Sends a single action over the channel, filling in the message id
+    fn send_action(&self, mut action: JsonValue) -> Result<Response> {
+        action["id"] = self.next_message_id().into();
+        let body = json::array![action];
+        self.ship_interface.send_put_request(&self.url, &body)
+    }
+
+    /// Acquires the current message id while also increasing it by 1
+    fn next_message_id(&self) -> u64 {
+        self.message_id_count.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Locks the map of subscription senders
+    fn subscriptions(
+        &self,
+    ) -> Result<std::sync::MutexGuard<'_, HashMap<CreationID, SubscriptionSender>>> {
+        self.subscriptions
+            .lock()
+            .map_err(|_| UrbitAPIError::Other("Shared channel subscriptions poisoned".to_string()))
+    }
+
+    /// Locks the graph-store subscription shared by the `MessageSubscription`s
+    fn message_feed(&self) -> Result<std::sync::MutexGuard<'_, MessageFeed>> {
+        self.message_feed
+            .lock()
+            .map_err(|_| UrbitAPIError::Other("Shared channel message feed poisoned".to_string()))
+    }
+
+    /// Passes an error of the event stream to the `on_stream_error` callback, if any
+    fn dispatch_stream_error(&self, error: &UrbitAPIError) {
+        if let Ok(mut on_stream_error) = self.on_stream_error.lock() {
+            if let Some(on_stream_error) = on_stream_error.as_mut() {
+                on_stream_error(error);
+            }
+        }
+    }
+
+    /// Dispatches an SSE event to the subscription it belongs to
+    fn dispatch_event(&self, event: &Event) {
+        let json = match json::parse(&event.data) {
+            Ok(json) => json,
+            Err(_) => return,
+        };
+        if let Some(id) = json["id"].as_u64() {
+            if let Ok(mut subscriptions) = self.subscriptions.lock() {
+                match json["response"].as_str() {
+                    Some("diff") => {
+                        // Drop the sender if the receiver was dropped
+                        let sent = subscriptions.get(&id).map(|s| {
+                            if s.raw_events {
+                                s.sender.send(json.clone())
+                            } else {
+                                s.sender.send(json["json"].clone())
+                            }
+                        });
+                        if let Some(Err(_)) = sent {
+                            subscriptions.remove(&id);
+                        }
+                    }
+                    // The subscription was ended by the ship or failed to be created
+                    Some("quit") => {
+                        subscriptions.remove(&id);
+                    }
+                    Some("subscribe") if !json["err"].is_null() => {
+                        subscriptions.remove(&id);
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    /// Acks every event up to and including the event id
+    fn ack(&self, event_id: u64) {
+        let _ack_res = self.send_action(object! {
+            "action": "ack",
+            "event-id": event_id,
+        });
+    }
+}
+
+impl Drop for SharedChannelInner {
+    fn drop(&mut self) {
+        let body = json::array![object! {
+            "id": self.next_message_id(),
+            "action": "delete",
+        }];
+        let _res = self.ship_interface.send_put_request(&self.url, &body);
+    }
+}
+
+impl fmt::Debug for SharedChannelInner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedChannelInner")
+            .field("ship_interface", &self.ship_interface)
+            .field("uid", &self.uid)
+            .field("url", &self.url)
+            .field("message_id_count", &self.message_id_count)
+            .field("subscriptions", &self.subscriptions)
+            .field("message_feed", &self.message_feed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Deref for MessageSubscription {
+    type Target = Receiver<AuthoredMessage>;
+
+    fn deref(&self) -> &Receiver<AuthoredMessage> {
+        &self.receiver
+    }
+}
+
+impl Drop for MessageSubscription {
+    fn drop(&mut self) {
+        let mut feed = match self.channel.inner.message_feed() {
+            Ok(feed) => feed,
+            Err(_) => return,
+        };
+        feed.watchers.remove(&self.watcher_id);
+        // End the shared graph-store subscription once nobody is watching
+        if feed.watchers.is_empty() {
+            if let Some(creation_id) = feed.creation_id.take() {
+                let _res = self.channel.unsubscribe(creation_id);
+            }
+        }
+    }
+}
+
+/// The body of the background reader thread of a `SharedChannel`. Blocks on the SSE
+/// stream and dispatches every event, stopping once all handles have been dropped.
+/// The events which are available at once are acked together with a single ack.
+fn read_events(event_receiver: EventReceiver, inner: Weak<SharedChannelInner>) {
+    while let Ok(first) = event_receiver.recv() {
+        let inner = match inner.upgrade() {
+            Some(inner) => inner,
+            None => break,
+        };
+        let mut pending_ack: Option<u64> = None;
+        for event_res in std::iter::once(first).chain(event_receiver.try_iter()) {
+            match event_res {
+                Ok(event) => {
+                    inner.dispatch_event(&event);
+                    if let Some(eid) = event.id.as_ref().and_then(|id| id.parse::<u64>().ok()) {
+                        pending_ack = Some(pending_ack.map_or(eid, |pending| pending.max(eid)));
+                    }
+                }
+                Err(e) => inner.dispatch_stream_error(&e),
+            }
+        }
+        if let Some(eid) = pending_ack {
+            inner.ack(eid);
+        }
+    }
+}
+
+/// The body of the thread which passes the messages of the shared graph-store
+/// subscription to every `MessageSubscription` watching the chat/DM they belong to.
+/// Stops once the graph-store subscription ends, disconnecting every `MessageSubscription`
+/// so that the next call to `subscribe_to_messages` creates a new graph-store subscription.
+fn fan_out_messages(subscription: SharedSubscription, inner: Weak<SharedChannelInner>) {
+    // Blocks until the next graph-store update arrives
+    for json in subscription.receiver.iter() {
+        let inner = match inner.upgrade() {
+            Some(inner) => inner,
+            None => break,
+        };
+        let node = match Node::from_graph_update_json(&json) {
+            Ok(node) => node,
+            Err(_) => continue,
+        };
+        let feed = match inner.message_feed() {
+            Ok(feed) => feed,
+            Err(_) => break,
+        };
+        for watcher in feed.watchers.values() {
+            if check_resource_json(&watcher.resource_ship, &watcher.resource_name, &json) {
+                let _res = watcher.sender.send(AuthoredMessage::from_node(&node));
+            }
+        }
+    }
+
+    // The ship ended the graph-store subscription, so reset the feed unless it has
+    // already been replaced (or cleared by `delete_channel`)
+    if let Some(inner) = inner.upgrade() {
+        if let Ok(mut feed) = inner.message_feed() {
+            if feed.creation_id == Some(subscription.creation_id) {
+                feed.creation_id = None;
+                feed.watchers.clear();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam::channel::RecvTimeoutError;
+    use std::time::Duration;
+
+    /// Creates a `SharedChannel` for a ship which is not running, without opening it
+    fn offline_channel() -> SharedChannel {
+        let ship_interface = ShipInterface::offline();
+        SharedChannel {
+            inner: Arc::new(SharedChannelInner {
+                url: format!("{}/~/channel/1", ship_interface.url),
+                ship_interface,
+                uid: "1".to_string(),
+                message_id_count: AtomicU64::new(1),
+                subscriptions: Mutex::new(HashMap::new()),
+                message_feed: Mutex::new(MessageFeed::default()),
+                on_stream_error: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Creates a raw SSE event with the given json data
+    fn event(data: JsonValue) -> Event {
+        let mut event = Event::new();
+        event.data = data.dump();
+        event
+    }
+
+    #[test]
+    // Verify that the message feed is reset once the ship quits the graph-store subscription
+    fn resets_message_feed_on_quit() {
+        let channel = offline_channel();
+        // Set up the graph-store subscription of the feed, as subscribing needs a ship
+        let (s, r) = unbounded();
+        channel.inner.subscriptions().unwrap().insert(
+            5,
+            SubscriptionSender {
+                sender: s,
+                raw_events: false,
+            },
+        );
+        let subscription = SharedSubscription {
+            creation_id: 5,
+            app: "graph-store".to_string(),
+            path: "/updates".to_string(),
+            receiver: r,
+        };
+        channel.inner.message_feed().unwrap().creation_id = Some(5);
+        let weak_inner = Arc::downgrade(&channel.inner);
+        let fan_out = thread::spawn(move || fan_out_messages(subscription, weak_inner));
+
+        let messages = channel.subscribe_to_messages("~zod", "chat").unwrap();
+        channel
+            .inner
+            .dispatch_event(&event(object! {"id": 5, "response": "quit"}));
+        fan_out.join().unwrap();
+
+        // Existing subscriptions are disconnected rather than blocking forever
+        assert!(matches!(
+            messages.recv_timeout(Duration::from_secs(1)),
+            Err(RecvTimeoutError::Disconnected)
+        ));
+        let feed = channel.inner.message_feed().unwrap();
+        assert_eq!(feed.creation_id, None);
+        assert!(feed.watchers.is_empty());
+        drop(feed);
+        // New subscriptions create a new graph-store subscription (which fails offline)
+        assert!(channel.subscribe_to_messages("~zod", "chat").is_err());
+    }
+}
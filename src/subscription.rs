@@ -1,9 +1,51 @@
+use crate::channel::Channel;
+use crate::error::{Result, UrbitAPIError};
+use crossbeam::channel::{unbounded, Receiver};
 use eventsource_threaded::event::Event;
 use json;
+use json::JsonValue;
+use std::collections::VecDeque;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 // ID of the message that created a `Subscription`
 pub type CreationID = u64;
 
+/// A callback which is called with the json of every fact (event) of a subscription
+pub type FactCallback = Box<dyn FnMut(&JsonValue) + Send>;
+/// A callback which is called once a subscription has been ended by the ship
+pub type QuitCallback = Box<dyn FnMut() + Send>;
+/// A callback which is called with the error if the ship rejects a subscription
+pub type ErrorCallback = Box<dyn FnMut(&str) + Send>;
+
+/// What a `Subscription` does with new messages once its buffer is at capacity
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverflowPolicy {
+    /// Discard the oldest buffered message to make room for the new one
+    DropOldest,
+    /// Discard the new message, keeping the buffered ones
+    DropNewest,
+    /// Stop acking (and reading) events on the `Channel` until messages have been
+    /// popped, applying backpressure to the ship rather than losing messages
+    BlockAcks,
+}
+
+/// The result of offering an event to a `Subscription`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventOffer {
+    /// The event is not for this subscription
+    NotMatched,
+    /// The event was handled (buffered or dropped as per the overflow policy)
+    /// and can be acked
+    Accepted,
+    /// The buffer is full and the `OverflowPolicy` is `BlockAcks`, so the
+    /// event must be held and offered again later
+    Blocked,
+}
+
 // A subscription on a given Channel
 #[derive(Debug, Clone)]
 pub struct Subscription {
@@ -16,10 +58,54 @@ pub struct Subscription {
     /// The path of the app being subscribed to
     pub path: String,
     // A list of messages from the given subscription.
-    pub message_list: Vec<String>,
+    pub message_list: VecDeque<String>,
+    /// The maximum number of messages held in `message_list` (unbounded if `None`)
+    pub capacity: Option<usize>,
+    /// What to do with new messages once `message_list` is at capacity
+    pub overflow_policy: OverflowPolicy,
+    /// The number of messages which have been dropped due to the buffer being full
+    pub dropped_count: u64,
+    /// Whether the ship has ended the subscription (or rejected it). An ended
+    /// `Subscription` is removed from its `Channel` once its messages have been popped.
+    pub ended: bool,
 }
 
 impl Subscription {
+    /// Create a new `Subscription` with an unbounded message buffer
+    pub fn new(channel_uid: &str, creation_id: CreationID, app: &str, path: &str) -> Subscription {
+        Subscription {
+            channel_uid: channel_uid.to_string(),
+            creation_id,
+            app: app.to_string(),
+            path: path.to_string(),
+            message_list: VecDeque::new(),
+            capacity: None,
+            overflow_policy: OverflowPolicy::DropOldest,
+            dropped_count: 0,
+            ended: false,
+        }
+    }
+
+    /// Bounds the message buffer to `capacity` messages, applying the overflow
+    /// policy once it is full. Already buffered messages beyond the capacity are kept.
+    /// Returns an `InvalidBufferCapacity` error if the capacity is 0.
+    pub fn set_buffer(&mut self, capacity: usize, overflow_policy: OverflowPolicy) -> Result<()> {
+        if capacity == 0 {
+            return Err(UrbitAPIError::InvalidBufferCapacity);
+        }
+        self.capacity = Some(capacity);
+        self.overflow_policy = overflow_policy;
+        Ok(())
+    }
+
+    /// Whether the message buffer is at (or over) capacity
+    pub fn is_full(&self) -> bool {
+        match self.capacity {
+            Some(capacity) => self.message_list.len() >= capacity,
+            None => false,
+        }
+    }
+
     /// Verifies if the id of the message id in the event matches thea
     /// `Subscription` `creation_id`.
     fn event_matches(&self, event: &Event) -> bool {
@@ -32,26 +118,248 @@ impl Subscription {
     /// Parses an event and adds it to the message list if it's id
     /// matches the `Subscription` `creation_id`. On success returns
     /// the length of the message list.
+    ///
+    /// If the buffer is full the overflow policy is applied. When the policy
+    /// is `BlockAcks` the event is not added and `None` is returned, use
+    /// `offer_event` to distinguish this from a non-matching event.
     pub fn add_to_message_list(&mut self, event: &Event) -> Option<u64> {
-        if self.event_matches(&event) {
-            let json = &json::parse(&event.data).ok()?["json"];
-            if !json.is_null() {
-                self.message_list.push(json.dump());
-                return Some(self.message_list.len() as u64);
+        match self.offer_event(event) {
+            EventOffer::Accepted => Some(self.message_list.len() as u64),
+            _ => None,
+        }
+    }
+
+    /// Offers an event to the `Subscription`, buffering it if it's id matches the
+    /// `Subscription` `creation_id` and applying the overflow policy if the buffer is full.
+    pub fn offer_event(&mut self, event: &Event) -> EventOffer {
+        if !self.event_matches(event) {
+            return EventOffer::NotMatched;
+        }
+        let json = match json::parse(&event.data) {
+            Ok(json) => json["json"].clone(),
+            Err(_) => return EventOffer::NotMatched,
+        };
+        if json.is_null() {
+            return EventOffer::NotMatched;
+        }
+
+        if self.is_full() {
+            match self.overflow_policy {
+                OverflowPolicy::DropOldest => {
+                    self.dropped_count += 1;
+                    // Without any buffered message to make room, the new one is the oldest
+                    if self.message_list.pop_front().is_none() {
+                        return EventOffer::Accepted;
+                    }
+                }
+                OverflowPolicy::DropNewest => {
+                    self.dropped_count += 1;
+                    return EventOffer::Accepted;
+                }
+                OverflowPolicy::BlockAcks => return EventOffer::Blocked,
             }
         }
-        None
+        self.message_list.push_back(json.dump());
+        EventOffer::Accepted
     }
 
     /// Pops a message from the front of `Subscription`'s `message_list`.
     /// If no messages are left, returns `None`.
     pub fn pop_message(&mut self) -> Option<String> {
-        if self.message_list.len() == 0 {
-            return None;
+        self.message_list.pop_front()
+    }
+}
+
+/// The callbacks registered for a `Subscription` on a `Channel`
+#[derive(Default)]
+pub(crate) struct SubscriptionCallbacks {
+    pub(crate) on_fact: Option<FactCallback>,
+    pub(crate) on_quit: Option<QuitCallback>,
+    pub(crate) on_error: Option<ErrorCallback>,
+}
+
+impl std::fmt::Debug for SubscriptionCallbacks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SubscriptionCallbacks")
+            .field("on_fact", &self.on_fact.is_some())
+            .field("on_quit", &self.on_quit.is_some())
+            .field("on_error", &self.on_error.is_some())
+            .finish()
+    }
+}
+
+/// A handle to a subscription which is processed on a background thread with its own
+/// `Channel`. Parsed messages are read via the handle's `Receiver` (which the handle
+/// dereferences to, so `handle.try_recv()` works as expected).
+///
+/// The background thread stops and deletes its `Channel` on the ship once the handle is
+/// dropped, `shutdown()` is called, or the `Receiver` has been disconnected.
+#[derive(Debug)]
+pub struct SubscriptionHandle<T> {
+    receiver: Receiver<T>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> SubscriptionHandle<T> {
+    /// Subscribes to the app/path using the provided `Channel`, and spawns a thread which
+    /// passes every message of the subscription through `parse`, sending the results
+    /// to the `Receiver` of the returned handle.
+    pub(crate) fn spawn<F>(
+        mut channel: Channel,
+        app: &str,
+        path: &str,
+        mut parse: F,
+    ) -> Result<SubscriptionHandle<T>>
+    where
+        F: FnMut(&JsonValue) -> Option<T> + Send + 'static,
+    {
+        if let Err(e) = channel.create_new_subscription(app, path) {
+            channel.delete_channel();
+            return Err(e);
+        }
+
+        let (s, r) = unbounded();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let app = app.to_string();
+        let path = path.to_string();
+
+        let thread = thread::spawn(move || {
+            let mut disconnected = false;
+            while !disconnected && !thread_stop.load(Ordering::SeqCst) {
+                channel.parse_event_messages();
+                if let Some(sub) = channel.find_subscription(&app, &path) {
+                    while let Some(mess) = sub.pop_message() {
+                        let parsed = json::parse(&mess).ok().and_then(|json| parse(&json));
+                        if let Some(item) = parsed {
+                            // The receiver was dropped, so nobody is listening anymore
+                            if s.send(item).is_err() {
+                                disconnected = true;
+                                break;
+                            }
+                        }
+                    }
+                }
+                // Pause for half a second
+                thread::sleep(Duration::new(0, 500000000));
+            }
+            channel.delete_channel();
+        });
+
+        Ok(SubscriptionHandle {
+            receiver: r,
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl<T> SubscriptionHandle<T> {
+    /// The `Receiver` which the parsed messages of the subscription are sent to
+    pub fn receiver(&self) -> &Receiver<T> {
+        &self.receiver
+    }
+
+    /// Stops the background thread, blocking until it has deleted its `Channel`
+    pub fn shutdown(mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
         }
-        let messages = self.message_list.clone();
-        let (head, tail) = messages.split_at(1);
-        self.message_list = tail.to_vec();
-        Some(head.to_owned()[0].clone())
+    }
+}
+
+impl<T> Deref for SubscriptionHandle<T> {
+    type Target = Receiver<T>;
+
+    fn deref(&self) -> &Receiver<T> {
+        &self.receiver
+    }
+}
+
+impl<T> Drop for SubscriptionHandle<T> {
+    fn drop(&mut self) {
+        // The thread notices on its next iteration and cleans up after itself
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an SSE event for the subscription with the given creation id
+    fn fact_event(creation_id: CreationID, fact: u64) -> Event {
+        let mut event = Event::new();
+        event.data = json::object! {"id": creation_id, "response": "diff", "json": fact}.dump();
+        event
+    }
+
+    /// Creates a subscription with a buffer of 2 messages which has been filled
+    fn full_subscription(overflow_policy: OverflowPolicy) -> Subscription {
+        let mut sub = Subscription::new("uid", 1, "app", "/path");
+        sub.set_buffer(2, overflow_policy).unwrap();
+        assert_eq!(sub.offer_event(&fact_event(1, 1)), EventOffer::Accepted);
+        assert_eq!(sub.offer_event(&fact_event(1, 2)), EventOffer::Accepted);
+        assert!(sub.is_full());
+        sub
+    }
+
+    #[test]
+    // Verify that only events with the creation id of the subscription are matched
+    fn ignores_other_events() {
+        let mut sub = Subscription::new("uid", 1, "app", "/path");
+        assert_eq!(sub.offer_event(&fact_event(2, 1)), EventOffer::NotMatched);
+        let mut poke_ack = Event::new();
+        poke_ack.data = json::object! {"id": 1, "response": "poke", "ok": "ok"}.dump();
+        assert_eq!(sub.offer_event(&poke_ack), EventOffer::NotMatched);
+        assert!(sub.message_list.is_empty());
+    }
+
+    #[test]
+    // Verify that the oldest message is dropped to make room for the new one
+    fn can_drop_oldest() {
+        let mut sub = full_subscription(OverflowPolicy::DropOldest);
+        assert_eq!(sub.offer_event(&fact_event(1, 3)), EventOffer::Accepted);
+        assert_eq!(sub.message_list, vec!["2", "3"]);
+        assert_eq!(sub.dropped_count, 1);
+    }
+
+    #[test]
+    // Verify that the new message is dropped, keeping the buffered ones
+    fn can_drop_newest() {
+        let mut sub = full_subscription(OverflowPolicy::DropNewest);
+        assert_eq!(sub.offer_event(&fact_event(1, 3)), EventOffer::Accepted);
+        assert_eq!(sub.message_list, vec!["1", "2"]);
+        assert_eq!(sub.dropped_count, 1);
+    }
+
+    #[test]
+    // Verify that a full buffer blocks new messages until one has been popped
+    fn can_block_acks() {
+        let mut sub = full_subscription(OverflowPolicy::BlockAcks);
+        assert_eq!(sub.offer_event(&fact_event(1, 3)), EventOffer::Blocked);
+        assert_eq!(sub.dropped_count, 0);
+        assert_eq!(sub.pop_message().as_deref(), Some("1"));
+        assert_eq!(sub.offer_event(&fact_event(1, 3)), EventOffer::Accepted);
+        assert_eq!(sub.message_list, vec!["2", "3"]);
+    }
+
+    #[test]
+    // Verify that a buffer without room for any message is rejected
+    fn rejects_zero_capacity() {
+        let mut sub = Subscription::new("uid", 1, "app", "/path");
+        assert!(matches!(
+            sub.set_buffer(0, OverflowPolicy::DropOldest),
+            Err(UrbitAPIError::InvalidBufferCapacity)
+        ));
+        assert_eq!(sub.capacity, None);
+
+        // A capacity of 0 set directly still never buffers anything
+        sub.capacity = Some(0);
+        assert_eq!(sub.offer_event(&fact_event(1, 1)), EventOffer::Accepted);
+        assert!(sub.message_list.is_empty());
+        assert_eq!(sub.dropped_count, 1);
     }
 }
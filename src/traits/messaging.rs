@@ -1,10 +1,8 @@
-use crate::error::{Result, UrbitAPIError};
+use crate::error::Result;
 use crate::graph::{Node, NodeContents};
+use crate::subscription::SubscriptionHandle;
 use crate::Channel;
-use crossbeam::channel::{unbounded, Receiver};
 use json::JsonValue;
-use std::thread;
-use std::time::Duration;
 
 /// A struct that represents a message that is to be submitted to Urbit.
 /// `Message` provides methods to build a message in chunks, thereby allowing you
@@ -64,17 +62,10 @@ pub trait Messaging {
     ) -> Result<String> {
         let node = self.channel().graph_store().new_node(message);
 
-        if let Ok(_) = self
-            .channel()
+        self.channel()
             .graph_store()
-            .add_node(resource_ship, resource_name, &node)
-        {
-            Ok(node.index)
-        } else {
-            Err(UrbitAPIError::FailedToSendChatMessage(
-                message.to_json().dump(),
-            ))
-        }
+            .add_node(resource_ship, resource_name, &node)?;
+        Ok(node.index)
     }
 
     /// Extracts messages automatically into a list of formatted `String`s
@@ -131,73 +122,40 @@ pub trait Messaging {
         Ok(nodes)
     }
 
-    /// Subscribe to and watch for messages. This method returns a `Receiver` with the
-    /// `AuthoredMessage`s that are posted after subscribing. Simply call `receiver.try_recv()`
+    /// Subscribe to and watch for messages. This method returns a `SubscriptionHandle` with the
+    /// `AuthoredMessage`s that are posted after subscribing. Simply call `handle.try_recv()`
     /// to read the next `AuthoredMessage` if one has been posted.
     ///
     /// Technical Note: This method actually creates a new `Channel` with your Urbit Ship, and spawns a new unix thread
     /// locally that processes all messages on said channel. This is required due to borrowing mechanisms in Rust, however
     /// on the plus side this makes it potentially more performant by each subscription having it's own unix thread.
+    /// The thread stops and deletes its `Channel` once the handle is dropped or shut down.
     fn subscribe_to_messages(
         &mut self,
         resource_ship: &str,
         resource_name: &str,
-    ) -> Result<Receiver<AuthoredMessage>> {
+    ) -> Result<SubscriptionHandle<AuthoredMessage>> {
         let resource_ship = resource_ship.to_string();
         let resource_name = resource_name.to_string();
-        // Create sender/receiver
-        let (s, r) = unbounded();
         // Creating a new Ship Interface Channel to pass into the new thread
         // to be used to communicate with the Urbit ship
-        let mut new_channel = self.channel().ship_interface.create_channel()?;
-
-        thread::spawn(move || {
-            // Infinitely watch for new graph store updates
-            let channel = &mut new_channel;
-            channel
-                .create_new_subscription("graph-store", "/updates")
-                .ok();
-            loop {
-                channel.parse_event_messages();
-                let res_graph_updates = &mut channel.find_subscription("graph-store", "/updates");
-                if let Some(graph_updates) = res_graph_updates {
-                    // Read all of the current SSE messages to find if any are for the resource
-                    // we are looking for.
-                    loop {
-                        let pop_res = graph_updates.pop_message();
-                        // Acquire the message
-                        if let Some(mess) = &pop_res {
-                            // Parse it to json
-                            if let Ok(json) = json::parse(mess) {
-                                // If the graph-store node update is not for the correct resource
-                                // then continue to next message.
-                                if !check_resource_json(&resource_ship, &resource_name, &json) {
-                                    continue;
-                                }
-                                // Otherwise, parse json to a `Node`
-                                if let Ok(node) = Node::from_graph_update_json(&json) {
-                                    // Parse it as an `AuthoredMessage`
-                                    let authored_message = AuthoredMessage::from_node(&node);
-                                    let _ = s.send(authored_message);
-                                }
-                            }
-                        }
-                        // If no messages left, stop
-                        if let None = &pop_res {
-                            break;
-                        }
-                    }
-                }
-                // Pause for half a second
-                thread::sleep(Duration::new(0, 500000000));
+        let new_channel = self.channel().ship_interface.create_channel()?;
+
+        SubscriptionHandle::spawn(new_channel, "graph-store", "/updates", move |json| {
+            // If the graph-store node update is not for the correct resource
+            // then skip the message.
+            if !check_resource_json(&resource_ship, &resource_name, json) {
+                return None;
             }
-        });
-        Ok(r)
+            // Otherwise, parse json to a `Node` and then an `AuthoredMessage`
+            let node = Node::from_graph_update_json(json).ok()?;
+            Some(AuthoredMessage::from_node(&node))
+        })
     }
 }
 
 /// Checks whether the resource json matches the resource_name & resource_ship
-fn check_resource_json(
+pub(crate) fn check_resource_json(
     resource_ship: &str,
     resource_name: &str,
     resource_json: &JsonValue,